@@ -15,19 +15,20 @@
 * along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 
 use super::{
     AdditiveGroup, BooleanAlgebra, BoundedPartialOrder, ClassicalDomain, DirectedGraph, Domain,
     Lattice, Monoid, PartialOrder, Ring, Semigroup, TwoElementAlg, UnitaryRing, TWO_ELEMENT_ALG,
 };
-use crate::core::{create_solver, Literal, SatSolver};
+use crate::core::{create_solver, Interrupt, Literal, SatSolver, SolveResult};
 
 /// The free boolean algebra backed by a SAT solver.
 pub struct FreeBooleanAlg {
     solver: Cell<Option<Box<dyn SatSolver>>>,
     unit: Literal,
     zero: Literal,
+    scopes: RefCell<Vec<Literal>>,
 }
 
 impl Default for FreeBooleanAlg {
@@ -44,7 +45,13 @@ impl FreeBooleanAlg {
         let zero = solver.negate(unit);
         solver.add_clause(&[unit]);
         let solver = Cell::new(Some(solver));
-        Self { solver, unit, zero }
+        let scopes = Default::default();
+        Self {
+            solver,
+            unit,
+            zero,
+            scopes,
+        }
     }
 
     /// Takes the solver out of its cell, performs the given operation with the solver and then
@@ -68,6 +75,45 @@ impl FreeBooleanAlg {
     pub fn add_generator(&self) -> Literal {
         self.mutate(|solver| solver.add_variable())
     }
+
+    /// Adds the given element as an axiom, so it becomes equal to the top
+    /// element and the algebra turns into a quotient of the free algebra.
+    /// Within a scope the axiom is retracted by the matching `pop`.
+    pub fn add_axiom(&self, elem: &Literal) {
+        let scopes = self.scopes.borrow();
+        self.mutate(|solver| match scopes.last() {
+            Some(&active) => {
+                let inactive = solver.negate(active);
+                solver.add_clause(&[*elem, inactive]);
+            }
+            None => solver.add_clause(&[*elem]),
+        })
+    }
+
+    /// Opens a new scope, the axioms added until the matching `pop` are
+    /// guarded by a fresh activation literal that is assumed in every
+    /// comparison while the scope is open.
+    pub fn push(&self) {
+        let active = self.mutate(|solver| solver.add_variable());
+        self.scopes.borrow_mut().push(active);
+    }
+
+    /// Closes the innermost scope and retracts the axioms added within it.
+    /// The elements created within the scope remain valid. Panics if there
+    /// is no open scope.
+    pub fn pop(&self) {
+        let active = self.scopes.borrow_mut().pop().expect("no open scope");
+        self.mutate(|solver| {
+            let inactive = solver.negate(active);
+            solver.add_clause(&[inactive]);
+        })
+    }
+
+    /// Returns a handle to interrupt the solver from another thread, if the
+    /// solver supports it. Comparisons panic once the solver is interrupted.
+    pub fn interrupt_handle(&self) -> Option<Interrupt> {
+        self.mutate(|solver| solver.interrupt_handle())
+    }
 }
 
 impl Domain for FreeBooleanAlg {
@@ -95,9 +141,13 @@ impl ClassicalDomain for FreeBooleanAlg {}
 
 impl DirectedGraph for FreeBooleanAlg {
     fn edge(&self, elem0: &Self::Elem, elem1: &Self::Elem) -> <Self::Logic as Domain>::Elem {
+        let mut assumptions = self.scopes.borrow().clone();
         self.mutate(|solver| {
-            let not_elem1 = solver.negate(*elem1);
-            !solver.solve_with(&[*elem0, not_elem1])
+            assumptions.push(*elem0);
+            assumptions.push(solver.negate(*elem1));
+            let result = solver.solve_with(&assumptions);
+            assert_ne!(result, SolveResult::Unknown, "solver was interrupted");
+            result == SolveResult::Unsat
         })
     }
 }
@@ -218,4 +268,28 @@ mod tests {
         let d = alg.join(&alg.meet(&z, &x), &alg.meet(&z, &y));
         assert!(alg.equals(&c, &d));
     }
+
+    #[test]
+    fn scopes() {
+        let alg = FreeBooleanAlg::new("");
+        let x = alg.add_generator();
+        let y = alg.add_generator();
+        let a = alg.meet(&x, &y);
+
+        alg.push();
+        alg.add_axiom(&x);
+        assert!(alg.equals(&a, &y));
+        alg.push();
+        alg.add_axiom(&alg.not(&y));
+        assert!(alg.equals(&a, &alg.zero()));
+        assert!(alg.equals(&x, &alg.unit()));
+        alg.pop();
+        assert!(!alg.equals(&y, &alg.zero()));
+        alg.pop();
+
+        assert!(!alg.equals(&a, &y));
+        assert!(!alg.equals(&x, &alg.unit()));
+        alg.add_axiom(&y);
+        assert!(alg.equals(&a, &x));
+    }
 }
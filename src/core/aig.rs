@@ -0,0 +1,597 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! And-inverter graphs that are lowered into a SAT solver on demand.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use super::{
+    counting, create_solver, cubes, BigNum, BoolAlg, BoolSat, Boolean, CubeConfig, Formula, GenVec,
+    Interrupted, Limits, Literal, ParModels, SatSolver, SolveResult,
+};
+
+/// An edge of an and-inverter graph, which is a node with an optional
+/// negation. The constant false node has index zero.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct AigEdge {
+    value: u32,
+}
+
+impl AigEdge {
+    /// Returns the index of the node of this edge.
+    fn node(self) -> usize {
+        (self.value >> 1) as usize
+    }
+
+    /// Returns true if this edge negates its node.
+    fn is_negated(self) -> bool {
+        self.value & 1 != 0
+    }
+}
+
+/// A node of an and-inverter graph.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum AigNode {
+    /// The constant false node.
+    Zero,
+    /// The input with the given index.
+    Input(usize),
+    /// The conjunction of two edges pointing to earlier nodes.
+    And(AigEdge, AigEdge),
+}
+
+/// The places where the lowered clauses can be added.
+trait ClauseSink {
+    fn add_variable(&mut self) -> Literal;
+
+    fn negate(&self, lit: Literal) -> Literal;
+
+    fn add_clause(&mut self, lits: &[Literal]);
+}
+
+impl ClauseSink for Box<dyn SatSolver> {
+    fn add_variable(&mut self) -> Literal {
+        self.as_mut().add_variable()
+    }
+
+    fn negate(&self, lit: Literal) -> Literal {
+        self.as_ref().negate(lit)
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        self.as_mut().add_clause(lits)
+    }
+}
+
+impl ClauseSink for Formula {
+    fn add_variable(&mut self) -> Literal {
+        Formula::add_variable(self)
+    }
+
+    fn negate(&self, lit: Literal) -> Literal {
+        Formula::negate(lit)
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        Formula::add_clause(self, lits)
+    }
+}
+
+/// Returns the literal of the given edge in the sink, and adds the Tseitin
+/// encoding of the nodes of its cone that are not yet lowered.
+fn lower<SINK>(
+    nodes: &[AigNode],
+    lowered: &mut Vec<Option<Literal>>,
+    sink: &mut SINK,
+    edge: AigEdge,
+) -> Literal
+where
+    SINK: ClauseSink,
+{
+    lowered.resize(nodes.len(), None);
+    let mut stack = vec![edge.node()];
+    while let Some(&node) = stack.last() {
+        if lowered[node].is_some() {
+            stack.pop();
+            continue;
+        }
+        match nodes[node] {
+            AigNode::Zero => {
+                let lit = sink.add_variable();
+                sink.add_clause(&[sink.negate(lit)]);
+                lowered[node] = Some(lit);
+            }
+            AigNode::Input(_) => {
+                lowered[node] = Some(sink.add_variable());
+            }
+            AigNode::And(edge1, edge2) => match (lowered[edge1.node()], lowered[edge2.node()]) {
+                (Some(lit1), Some(lit2)) => {
+                    let lit1 = if edge1.is_negated() {
+                        sink.negate(lit1)
+                    } else {
+                        lit1
+                    };
+                    let lit2 = if edge2.is_negated() {
+                        sink.negate(lit2)
+                    } else {
+                        lit2
+                    };
+                    let lit3 = sink.add_variable();
+                    let not_lit3 = sink.negate(lit3);
+                    sink.add_clause(&[not_lit3, lit1]);
+                    sink.add_clause(&[not_lit3, lit2]);
+                    sink.add_clause(&[lit3, sink.negate(lit1), sink.negate(lit2)]);
+                    lowered[node] = Some(lit3);
+                }
+                (lit1, lit2) => {
+                    if lit1.is_none() {
+                        stack.push(edge1.node());
+                    }
+                    if lit2.is_none() {
+                        stack.push(edge2.node());
+                    }
+                }
+            },
+        }
+    }
+
+    let lit = lowered[edge.node()].unwrap();
+    if edge.is_negated() {
+        sink.negate(lit)
+    } else {
+        lit
+    }
+}
+
+/// A boolean algebra whose elements are the edges of an and-inverter graph
+/// with constant propagation and structural hashing. The graph is lowered
+/// into the SAT solver with the Tseitin encoding on demand, only the cones
+/// of the clauses and assumptions reach the solver. The graph can also be
+/// evaluated in any other boolean algebra and exported in the AIGER format.
+#[derive(Debug)]
+pub struct Aig {
+    nodes: Vec<AigNode>,
+    inputs: Vec<usize>,
+    hash: HashMap<(AigEdge, AigEdge), AigEdge>,
+    clauses: Vec<Vec<AigEdge>>,
+    solver: Box<dyn SatSolver>,
+    lowered: Vec<Option<Literal>>,
+}
+
+impl Aig {
+    /// Creates a new empty graph backed by the named SAT solver.
+    pub fn new(solver_name: &str) -> Self {
+        Aig::with_solver(create_solver(solver_name))
+    }
+
+    /// Creates a new empty graph backed by the given SAT solver.
+    pub fn with_solver(solver: Box<dyn SatSolver>) -> Self {
+        Aig {
+            nodes: vec![AigNode::Zero],
+            inputs: Vec::new(),
+            hash: HashMap::new(),
+            clauses: Vec::new(),
+            solver,
+            lowered: Vec::new(),
+        }
+    }
+
+    /// Returns the number of inputs in the graph.
+    pub fn num_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns the number of and gates in the graph.
+    pub fn num_ands(&self) -> usize {
+        self.nodes.len() - self.inputs.len() - 1
+    }
+
+    /// Returns the number of variables lowered into the SAT solver.
+    pub fn num_lowered(&self) -> usize {
+        self.lowered.iter().filter(|lit| lit.is_some()).count()
+    }
+
+    /// Returns the conjunction of the clauses added so far.
+    pub fn constraint(&mut self) -> AigEdge {
+        let clauses = self.clauses.clone();
+        let mut result = self.bool_unit();
+        for clause in clauses {
+            let elem = self.bool_fold_any(clause.into_iter());
+            result = self.bool_and(result, elem);
+        }
+        result
+    }
+
+    /// Evaluates the given edges in the given boolean algebra, where the
+    /// inputs of the graph are replaced with the given elements in their
+    /// order of creation.
+    pub fn evaluate<ALG>(
+        &self,
+        alg: &mut ALG,
+        inputs: &[ALG::Elem],
+        edges: &[AigEdge],
+    ) -> Vec<ALG::Elem>
+    where
+        ALG: BoolAlg,
+    {
+        assert_eq!(inputs.len(), self.inputs.len());
+        let last = edges.iter().map(|edge| edge.node()).max().unwrap_or(0);
+        let get = |alg: &mut ALG, values: &[ALG::Elem], edge: AigEdge| {
+            let elem = values[edge.node()].clone();
+            if edge.is_negated() {
+                alg.bool_not(elem)
+            } else {
+                elem
+            }
+        };
+
+        let mut values: Vec<ALG::Elem> = Vec::with_capacity(last + 1);
+        for node in self.nodes[..=last].iter() {
+            let elem = match *node {
+                AigNode::Zero => alg.bool_zero(),
+                AigNode::Input(index) => inputs[index].clone(),
+                AigNode::And(edge1, edge2) => {
+                    let elem1 = get(alg, &values, edge1);
+                    let elem2 = get(alg, &values, edge2);
+                    alg.bool_and(elem1, elem2)
+                }
+            };
+            values.push(elem);
+        }
+
+        edges.iter().map(|&edge| get(alg, &values, edge)).collect()
+    }
+
+    /// Writes the cone of the given edges as outputs in the ASCII AIGER
+    /// format. All inputs are written in their order of creation, but only
+    /// the needed and gates are kept and renumbered.
+    pub fn write_aiger(&self, output: &mut dyn io::Write, edges: &[AigEdge]) -> io::Result<()> {
+        let mut needed: HashSet<usize> = HashSet::new();
+        let mut stack: Vec<usize> = edges.iter().map(|edge| edge.node()).collect();
+        while let Some(node) = stack.pop() {
+            if let AigNode::And(edge1, edge2) = self.nodes[node] {
+                if needed.insert(node) {
+                    stack.push(edge1.node());
+                    stack.push(edge2.node());
+                }
+            }
+        }
+
+        // inputs come first, then the gates in topological order
+        let mut index: Vec<u32> = vec![0; self.nodes.len()];
+        for (pos, &node) in self.inputs.iter().enumerate() {
+            index[node] = pos as u32 + 1;
+        }
+        let mut ands: Vec<usize> = needed.into_iter().collect();
+        ands.sort_unstable();
+        for (pos, &node) in ands.iter().enumerate() {
+            index[node] = (self.inputs.len() + pos) as u32 + 1;
+        }
+        let map = |edge: AigEdge| 2 * index[edge.node()] + edge.is_negated() as u32;
+
+        writeln!(
+            output,
+            "aag {} {} 0 {} {}",
+            self.inputs.len() + ands.len(),
+            self.inputs.len(),
+            edges.len(),
+            ands.len()
+        )?;
+        for pos in 0..self.inputs.len() {
+            writeln!(output, "{}", 2 * (pos + 1))?;
+        }
+        for &edge in edges {
+            writeln!(output, "{}", map(edge))?;
+        }
+        for &node in ands.iter() {
+            if let AigNode::And(edge1, edge2) = self.nodes[node] {
+                let (lit1, lit2) = (map(edge1), map(edge2));
+                writeln!(
+                    output,
+                    "{} {} {}",
+                    2 * index[node],
+                    lit1.max(lit2),
+                    lit1.min(lit2)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the solver literal of the given edge, lowering its cone.
+    fn lower(&mut self, edge: AigEdge) -> Literal {
+        lower(&self.nodes, &mut self.lowered, &mut self.solver, edge)
+    }
+
+    /// Returns the solver literal of an edge that was already lowered.
+    fn lowered(&self, edge: AigEdge) -> Literal {
+        let lit = self.lowered[edge.node()].expect("edge is not lowered");
+        if edge.is_negated() {
+            self.solver.negate(lit)
+        } else {
+            lit
+        }
+    }
+
+    /// Returns the formula of the clauses with the given edges and split
+    /// edges lowered, and the literals of the edges and of the split edges
+    /// in the formula.
+    fn formula<ITER>(&self, edges: ITER, split: &[AigEdge]) -> (Formula, Vec<i32>, Vec<i32>)
+    where
+        ITER: Iterator<Item = AigEdge>,
+    {
+        let mut formula = Formula::new();
+        let mut lowered = Vec::new();
+        for clause in self.clauses.iter() {
+            let lits: Vec<Literal> = clause
+                .iter()
+                .map(|&edge| lower(&self.nodes, &mut lowered, &mut formula, edge))
+                .collect();
+            formula.add_clause(&lits);
+        }
+        let mut encode =
+            |edge| Formula::encode(lower(&self.nodes, &mut lowered, &mut formula, edge));
+        let lits = edges.map(&mut encode).collect();
+        let split = split.iter().copied().map(&mut encode).collect();
+        (formula, lits, split)
+    }
+}
+
+impl BoolAlg for Aig {
+    type Elem = AigEdge;
+
+    fn bool_lift(&self, elem: bool) -> Self::Elem {
+        AigEdge { value: elem as u32 }
+    }
+
+    fn bool_not(&mut self, elem: Self::Elem) -> Self::Elem {
+        AigEdge {
+            value: elem.value ^ 1,
+        }
+    }
+
+    fn bool_and(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        let zero = self.bool_zero();
+        let unit = self.bool_unit();
+        if elem1 == zero || elem2 == zero || elem1.value == elem2.value ^ 1 {
+            zero
+        } else if elem1 == unit || elem1 == elem2 {
+            elem2
+        } else if elem2 == unit {
+            elem1
+        } else {
+            let key = (elem1.min(elem2), elem1.max(elem2));
+            if let Some(&elem) = self.hash.get(&key) {
+                return elem;
+            }
+            let elem = AigEdge {
+                value: 2 * self.nodes.len() as u32,
+            };
+            self.nodes.push(AigNode::And(key.0, key.1));
+            self.hash.insert(key, elem);
+            elem
+        }
+    }
+
+    fn bool_or(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        let not_elem1 = self.bool_not(elem1);
+        let not_elem2 = self.bool_not(elem2);
+        let elem = self.bool_and(not_elem1, not_elem2);
+        self.bool_not(elem)
+    }
+
+    fn bool_xor(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        let not_elem1 = self.bool_not(elem1);
+        let not_elem2 = self.bool_not(elem2);
+        let elem3 = self.bool_and(elem1, not_elem2);
+        let elem4 = self.bool_and(not_elem1, elem2);
+        self.bool_or(elem3, elem4)
+    }
+}
+
+impl BoolSat for Aig {
+    fn bool_add_variable(&mut self) -> Self::Elem {
+        let elem = AigEdge {
+            value: 2 * self.nodes.len() as u32,
+        };
+        self.nodes.push(AigNode::Input(self.inputs.len()));
+        self.inputs.push(elem.node());
+        elem
+    }
+
+    fn bool_add_clause(&mut self, clause: &[Self::Elem]) {
+        self.clauses.push(clause.to_vec());
+        let lits: Vec<Literal> = clause.iter().map(|&edge| self.lower(edge)).collect();
+        self.solver.add_clause(&lits);
+    }
+
+    /// The inputs outside of the lowered cones are false in the model.
+    fn bool_find_one_model_limited<ITER>(
+        &mut self,
+        assumptions: &[Self::Elem],
+        literals: ITER,
+        limits: &Limits,
+    ) -> Result<Option<GenVec<bool>>, Interrupted>
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let lits: Vec<Literal> = assumptions.iter().map(|&edge| self.lower(edge)).collect();
+        match self.solver.solve_limited(&lits, limits) {
+            SolveResult::Sat => {
+                let inputs: Vec<bool> = self
+                    .inputs
+                    .iter()
+                    .map(|&node| match self.lowered.get(node) {
+                        Some(Some(lit)) => self.solver.get_value(*lit),
+                        _ => false,
+                    })
+                    .collect();
+                let edges: Vec<AigEdge> = literals.collect();
+                let values = self.evaluate(&mut Boolean(), &inputs, &edges);
+                Ok(Some(values.into_iter().collect()))
+            }
+            SolveResult::Unsat => Ok(None),
+            SolveResult::Unknown => Err(Interrupted),
+        }
+    }
+
+    /// The assumptions must be the ones of the last call.
+    fn bool_failed_assumptions<ITER>(&self, assumptions: ITER) -> GenVec<bool>
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let failed: HashSet<Literal> = self.solver.failed_assumptions().into_iter().collect();
+        assumptions
+            .map(|edge| failed.contains(&self.lowered(edge)))
+            .collect()
+    }
+
+    /// The clauses and the given literals are lowered into a new formula,
+    /// so the SAT solver does not need to record it.
+    fn bool_count_models<ITER>(&self, literals: ITER) -> BigNum
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let (formula, projection, _) = self.formula(literals, &[]);
+        counting::count_models(&formula, &projection)
+    }
+
+    fn bool_par_find_num_models<ITER>(
+        &self,
+        literals: ITER,
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> usize
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let (formula, projection, split) = self.formula(literals, split);
+        cubes::count_models(formula, projection, split, config)
+    }
+
+    fn bool_par_models<ITER>(
+        &self,
+        literals: ITER,
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> ParModels
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let (formula, projection, split) = self.formula(literals, split);
+        cubes::models(formula, projection, split, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::GenVector as _;
+    use super::*;
+
+    #[test]
+    fn hashing() {
+        let mut alg = Aig::new("");
+        let a = alg.bool_add_variable();
+        let b = alg.bool_add_variable();
+        let c = alg.bool_and(a, b);
+        assert_eq!(alg.bool_and(b, a), c);
+        let d = alg.bool_not(a);
+        assert_eq!(alg.bool_and(a, d), alg.bool_zero());
+        assert_eq!(alg.bool_or(a, d), alg.bool_unit());
+        assert_eq!(alg.bool_and(c, alg.bool_unit()), c);
+        let e = alg.bool_xor(a, b);
+        let f = alg.bool_xor(a, b);
+        assert_eq!(e, f);
+        assert_eq!(alg.num_inputs(), 2);
+        assert_eq!(alg.num_ands(), 4);
+
+        let mut boolean = Boolean();
+        for bits in 0..4 {
+            let inputs = [bits & 1 != 0, bits & 2 != 0];
+            let values = alg.evaluate(&mut boolean, &inputs, &[c, e]);
+            assert_eq!(values, vec![inputs[0] && inputs[1], inputs[0] ^ inputs[1]]);
+        }
+    }
+
+    #[test]
+    fn solving() {
+        let mut alg = Aig::new("");
+        let elems: Vec<AigEdge> = (0..4).map(|_| alg.bool_add_variable()).collect();
+        let unused = alg.bool_fold_sum(elems.iter().copied());
+        let a = alg.bool_xor(elems[0], elems[1]);
+        alg.bool_add_clause(&[a]);
+        assert_eq!(alg.num_lowered(), 5);
+
+        let b = alg.bool_not(elems[0]);
+        let values = alg
+            .bool_find_one_model(&[b], elems.iter().copied().chain([unused]))
+            .unwrap();
+        assert!(!values.get(0) && values.get(1) && !values.get(2) && !values.get(3));
+        assert!(values.get(4));
+
+        let c = alg.bool_not(elems[1]);
+        let none = std::iter::empty();
+        assert!(alg.bool_find_one_model(&[b, elems[2], c], none).is_none());
+        let failed = alg.bool_failed_assumptions([b, elems[2], c].iter().copied());
+        assert!(failed.get(0) && !failed.get(1) && failed.get(2));
+
+        let count = alg.bool_count_models(elems.iter().copied());
+        assert_eq!(count, BigNum::from(8));
+        let count = alg.bool_count_models([unused, elems[0]].iter().copied());
+        assert_eq!(count, BigNum::from(4));
+        let split = [elems[2]];
+        let count =
+            alg.bool_par_find_num_models(elems.iter().copied(), &split, &Default::default());
+        assert_eq!(count, 8);
+        assert_eq!(alg.bool_find_num_models_method1(elems.into_iter()), 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "split literal is not projected")]
+    fn split_not_projected() {
+        let mut alg = Aig::new("");
+        let a = alg.bool_add_variable();
+        let b = alg.bool_add_variable();
+        let c = alg.bool_or(a, b);
+        alg.bool_add_clause(&[c]);
+        alg.bool_par_find_num_models([a].iter().copied(), &[b], &Default::default());
+    }
+
+    #[test]
+    fn aiger() {
+        let mut alg = Aig::new("");
+        let a = alg.bool_add_variable();
+        let b = alg.bool_add_variable();
+        let c = alg.bool_add_variable();
+        let unused = alg.bool_and(a, c);
+        let d = alg.bool_or(a, b);
+        alg.bool_add_clause(&[d, unused]);
+        let e = alg.bool_and(d, c);
+        let f = alg.bool_not(e);
+
+        let mut output = Vec::new();
+        alg.write_aiger(&mut output, &[f, b]).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(text, "aag 5 3 0 2 2\n2\n4\n6\n11\n4\n8 5 3\n10 9 6\n");
+
+        let constraint = alg.constraint();
+        let mut boolean = Boolean();
+        let values = alg.evaluate(&mut boolean, &[false, false, true], &[constraint]);
+        assert_eq!(values, vec![false]);
+    }
+}
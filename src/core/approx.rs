@@ -0,0 +1,231 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Approximate model counting with random XOR hash functions.
+
+use super::random::Random;
+use super::{BigNum, BoolSat, GenElem, GenVec, GenVector as _};
+
+/// The result of an approximate model count.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApproxCount {
+    /// The estimated number of models.
+    pub estimate: BigNum,
+    /// The estimate is within a factor of `1 + epsilon` of the real count
+    /// (in both directions) with probability at least `1 - delta`.
+    pub epsilon: f64,
+    /// The allowed probability of a wrong estimate.
+    pub delta: f64,
+    /// The number of hashing rounds whose median is the estimate.
+    pub iterations: usize,
+    /// True if there were few enough models to count them exactly.
+    pub exact: bool,
+}
+
+/// Returns an estimate of the number of models with respect to the given
+/// literals following the ApproxMC algorithm of Chakraborty, Meel and
+/// Vardi. The models are split into random cells by XOR constraints over
+/// the literals, the models of a single cell are counted up to a threshold,
+/// and the median of the scaled cell sizes is returned. The clauses added
+/// during the search are disabled afterwards with activation literals.
+pub(super) fn approx_count<ALG>(
+    alg: &mut ALG,
+    literals: Vec<ALG::Elem>,
+    epsilon: f64,
+    delta: f64,
+    seed: u64,
+) -> ApproxCount
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+{
+    assert!(epsilon > 0.0);
+    assert!(delta > 0.0 && delta < 1.0);
+
+    let threshold = 1.0 + 9.84 * (1.0 + epsilon / (1.0 + epsilon)) * (1.0 + 1.0 / epsilon).powi(2);
+    let threshold = threshold.ceil() as usize;
+    let iterations = (17.0 * (3.0 / delta).log2()).ceil() as usize;
+
+    let count = bounded_models(alg, &[], &literals, threshold).len();
+    if count < threshold {
+        return ApproxCount {
+            estimate: BigNum::from(count as u64),
+            epsilon,
+            delta,
+            iterations: 0,
+            exact: true,
+        };
+    }
+
+    let mut random = Random::new(seed);
+    let mut estimates: Vec<BigNum> = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let mut hash = Hash::new(&literals, &mut random);
+
+        // find the number of rows where the cells become small enough by
+        // doubling and then halving, there are many models without rows
+        let mut lower = 0;
+        let mut upper = 1.min(literals.len());
+        let mut upper_count = count;
+        while upper > lower {
+            let rows = hash.rows(alg, upper);
+            upper_count = bounded_models(alg, rows, &literals, threshold).len();
+            if upper_count < threshold {
+                break;
+            }
+            lower = upper;
+            upper = (2 * upper).min(literals.len());
+        }
+        while upper - lower > 1 {
+            let middle = (lower + upper) / 2;
+            let rows = hash.rows(alg, middle);
+            let count = bounded_models(alg, rows, &literals, threshold).len();
+            if count < threshold {
+                upper = middle;
+                upper_count = count;
+            } else {
+                lower = middle;
+            }
+        }
+
+        estimates.push(&BigNum::from(upper_count as u64) << upper);
+    }
+
+    estimates.sort();
+    ApproxCount {
+        estimate: estimates[estimates.len() / 2].clone(),
+        epsilon,
+        delta,
+        iterations,
+        exact: false,
+    }
+}
+
+/// A random hash function whose rows are XOR constraints over a random
+/// subset of the literals with a random parity, created on demand.
+pub(super) struct Hash<'a, ELEM> {
+    literals: &'a [ELEM],
+    rows: Vec<ELEM>,
+    random: &'a mut Random,
+}
+
+impl<'a, ELEM> Hash<'a, ELEM>
+where
+    ELEM: GenElem,
+{
+    /// Creates a new hash function over the given literals.
+    pub fn new(literals: &'a [ELEM], random: &'a mut Random) -> Self {
+        Hash {
+            literals,
+            rows: Vec::new(),
+            random,
+        }
+    }
+
+    /// Returns the first given number of rows of the hash function.
+    pub fn rows<ALG>(&mut self, alg: &mut ALG, count: usize) -> &[ELEM]
+    where
+        ALG: BoolSat<Elem = ELEM>,
+    {
+        while self.rows.len() < count {
+            let parity = alg.bool_lift(self.random.next_bool());
+            let mut elems = vec![parity];
+            for &lit in self.literals {
+                if self.random.next_bool() {
+                    elems.push(lit);
+                }
+            }
+            let row = alg.bool_fold_sum(elems.into_iter());
+            self.rows.push(row);
+        }
+        &self.rows[..count]
+    }
+}
+
+/// Returns the models with respect to the given literals under the given
+/// assumptions, but stops the enumeration at the given limit. The blocking
+/// clauses are guarded by a fresh activation literal, and they are retired
+/// at the end by the unit clause of its negation, so they do not constrain
+/// the later rounds and the solver can drop them as satisfied.
+pub(super) fn bounded_models<ALG>(
+    alg: &mut ALG,
+    assumptions: &[ALG::Elem],
+    literals: &[ALG::Elem],
+    limit: usize,
+) -> Vec<GenVec<bool>>
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+{
+    let active = alg.bool_add_variable();
+    let mut assumptions = assumptions.to_vec();
+    assumptions.push(active);
+
+    let mut models = Vec::new();
+    while models.len() < limit {
+        match alg.bool_find_one_model(&assumptions, literals.iter().copied()) {
+            Some(values) => {
+                // the blocked value of the activation literal is true
+                let pairs = literals.iter().copied().zip(values.iter());
+                alg.bool_add_blocking_clause(pairs.chain([(active, true)]));
+                models.push(values);
+            }
+            None => break,
+        }
+    }
+
+    let inactive = alg.bool_not(active);
+    alg.bool_add_clause(&[inactive]);
+    models
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{BoolAlg, CardEncoding, Solver};
+    use super::*;
+
+    #[test]
+    fn approx_count() {
+        let mut alg = Solver::new("");
+        let elems: Vec<_> = (0..8).map(|_| alg.bool_add_variable()).collect();
+        let few = alg.bool_fold_atmost(elems.iter().copied(), 1, CardEncoding::Totalizer);
+        let many = alg.bool_fold_atmost(elems.iter().copied(), 3, CardEncoding::Totalizer);
+
+        let result = super::approx_count(&mut alg, elems.clone(), 2.0, 0.9, 1);
+        let estimate = result.estimate.to_u64().unwrap();
+        assert!(!result.exact);
+        assert_eq!(result.iterations, 30);
+        assert!(3 * estimate >= 256 && estimate <= 3 * 256);
+
+        // the solver remains usable and small counts are exact
+        alg.bool_add_clause(&[many]);
+        let result = super::approx_count(&mut alg, elems.clone(), 2.0, 0.9, 2);
+        let estimate = result.estimate.to_u64().unwrap();
+        assert!(3 * estimate >= 93 && estimate <= 3 * 93);
+
+        alg.bool_add_clause(&[few]);
+        let result = super::approx_count(&mut alg, elems.clone(), 2.0, 0.9, 3);
+        assert!(result.exact);
+        assert_eq!(result.estimate, BigNum::from(9));
+
+        // the blocking clauses of the earlier rounds are retired
+        let models = bounded_models(&mut alg, &[], &elems, 100);
+        assert_eq!(models.len(), 9);
+        let models = bounded_models(&mut alg, &[], &elems, 100);
+        assert_eq!(models.len(), 9);
+    }
+}
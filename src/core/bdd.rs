@@ -0,0 +1,427 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Reduced ordered binary decision diagrams.
+
+use std::collections::HashMap;
+
+use super::{BigNum, BoolAlg};
+
+/// A handle of a node in a binary decision diagram. Equivalent functions
+/// have the same handle within the same diagram.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct BddNode {
+    index: u32,
+}
+
+/// The constant false node.
+const ZERO: BddNode = BddNode { index: 0 };
+
+/// The constant true node.
+const ONE: BddNode = BddNode { index: 1 };
+
+/// The variable of the constant nodes, which is after all real variables.
+const TERMINAL: u32 = u32::MAX;
+
+/// A decision node that branches on a variable.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct BddEntry {
+    var: u32,
+    low: BddNode,
+    high: BddNode,
+}
+
+/// The binary operations kept in the computed table.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum BddOp {
+    And,
+    Or,
+    Xor,
+}
+
+/// A boolean algebra of reduced ordered binary decision diagrams, where the
+/// variables are ordered by their creation. The number of models and the
+/// quantification over variables are computed directly on the diagrams, so
+/// this is suitable for small but dense problems.
+#[derive(Debug)]
+pub struct Bdd {
+    nodes: Vec<BddEntry>,
+    unique: HashMap<BddEntry, BddNode>,
+    computed: HashMap<(BddOp, BddNode, BddNode), BddNode>,
+    num_vars: u32,
+}
+
+impl Default for Bdd {
+    fn default() -> Self {
+        let terminal = |node| BddEntry {
+            var: TERMINAL,
+            low: node,
+            high: node,
+        };
+        Bdd {
+            nodes: vec![terminal(ZERO), terminal(ONE)],
+            unique: HashMap::new(),
+            computed: HashMap::new(),
+            num_vars: 0,
+        }
+    }
+}
+
+impl Bdd {
+    /// Creates a new diagram without variables.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a new variable after all existing ones in the order, and
+    /// returns the node of the variable.
+    pub fn add_variable(&mut self) -> BddNode {
+        let var = self.num_vars;
+        self.num_vars += 1;
+        self.make(var, ZERO, ONE)
+    }
+
+    /// Returns the number of variables.
+    pub fn num_variables(&self) -> usize {
+        self.num_vars as usize
+    }
+
+    /// Returns the number of nodes in the diagram including the terminals.
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the number of assignments of all variables that satisfy the
+    /// function of the node.
+    pub fn sat_count(&self, node: BddNode) -> BigNum {
+        let mut cache: HashMap<BddNode, BigNum> = HashMap::new();
+        let count = self.count(node, &mut cache);
+        &count << self.level(node) as usize
+    }
+
+    /// Returns the function of the node with the given variables
+    /// existentially quantified.
+    pub fn exists(&mut self, node: BddNode, vars: &[BddNode]) -> BddNode {
+        let mask = self.mask(vars);
+        let mut cache = HashMap::new();
+        self.quantify(node, &mask, &mut cache)
+    }
+
+    /// Returns the function of the node with the given variables
+    /// universally quantified.
+    pub fn forall(&mut self, node: BddNode, vars: &[BddNode]) -> BddNode {
+        let node = self.bool_not(node);
+        let node = self.exists(node, vars);
+        self.bool_not(node)
+    }
+
+    /// Returns an iterator over the assignments of the given variables that
+    /// can be extended to a model of the node. The values are returned in
+    /// the order of the given variables, but the models are enumerated in
+    /// the lexicographic order of the variables of the diagram.
+    pub fn models(&mut self, node: BddNode, vars: &[BddNode]) -> BddModels<'_> {
+        let mut mask = self.mask(vars);
+        mask.iter_mut().for_each(|b| *b = !*b);
+        let mut cache = HashMap::new();
+        let node = self.quantify(node, &mask, &mut cache);
+
+        let mut order: Vec<(u32, usize)> = vars
+            .iter()
+            .enumerate()
+            .map(|(pos, &var)| (self.variable(var), pos))
+            .collect();
+        order.sort_unstable();
+        order.dedup_by_key(|(var, _)| *var);
+        assert_eq!(order.len(), vars.len(), "repeated variables");
+
+        BddModels {
+            bdd: self,
+            order,
+            stack: vec![(node, 0, vec![false; vars.len()])],
+        }
+    }
+
+    /// Returns the variable of the node, which must be a variable created
+    /// by `add_variable`.
+    fn variable(&self, node: BddNode) -> u32 {
+        let entry = self.nodes[node.index as usize];
+        assert!(
+            entry.var != TERMINAL && entry.low == ZERO && entry.high == ONE,
+            "not a variable"
+        );
+        entry.var
+    }
+
+    /// Returns a mask marking the given variables.
+    fn mask(&self, vars: &[BddNode]) -> Vec<bool> {
+        let mut mask = vec![false; self.num_vars as usize];
+        for &var in vars {
+            mask[self.variable(var) as usize] = true;
+        }
+        mask
+    }
+
+    /// Returns the position of the variable of the node in the order, which
+    /// is the number of variables for the terminals.
+    fn level(&self, node: BddNode) -> u32 {
+        self.nodes[node.index as usize].var.min(self.num_vars)
+    }
+
+    /// Returns the node with the given variable and children, creating it
+    /// if it does not exist yet.
+    fn make(&mut self, var: u32, low: BddNode, high: BddNode) -> BddNode {
+        if low == high {
+            return low;
+        }
+        let entry = BddEntry { var, low, high };
+        if let Some(&node) = self.unique.get(&entry) {
+            return node;
+        }
+        let node = BddNode {
+            index: self.nodes.len() as u32,
+        };
+        self.nodes.push(entry);
+        self.unique.insert(entry, node);
+        node
+    }
+
+    /// Returns the children of the node with respect to the given variable.
+    fn cofactors(&self, node: BddNode, var: u32) -> (BddNode, BddNode) {
+        let entry = self.nodes[node.index as usize];
+        if entry.var == var {
+            (entry.low, entry.high)
+        } else {
+            (node, node)
+        }
+    }
+
+    /// Applies the binary operation to the nodes.
+    fn apply(&mut self, op: BddOp, node1: BddNode, node2: BddNode) -> BddNode {
+        match op {
+            BddOp::And => {
+                if node1 == ZERO || node2 == ZERO {
+                    return ZERO;
+                } else if node1 == ONE || node1 == node2 {
+                    return node2;
+                } else if node2 == ONE {
+                    return node1;
+                }
+            }
+            BddOp::Or => {
+                if node1 == ONE || node2 == ONE {
+                    return ONE;
+                } else if node1 == ZERO || node1 == node2 {
+                    return node2;
+                } else if node2 == ZERO {
+                    return node1;
+                }
+            }
+            BddOp::Xor => {
+                if node1 == node2 {
+                    return ZERO;
+                } else if node1 == ZERO {
+                    return node2;
+                } else if node2 == ZERO {
+                    return node1;
+                }
+            }
+        }
+
+        // all operations are commutative
+        let key = (op, node1.min(node2), node1.max(node2));
+        if let Some(&node) = self.computed.get(&key) {
+            return node;
+        }
+
+        let var = self.nodes[node1.index as usize]
+            .var
+            .min(self.nodes[node2.index as usize].var);
+        let (low1, high1) = self.cofactors(node1, var);
+        let (low2, high2) = self.cofactors(node2, var);
+        let low = self.apply(op, low1, low2);
+        let high = self.apply(op, high1, high2);
+        let node = self.make(var, low, high);
+        self.computed.insert(key, node);
+        node
+    }
+
+    /// Existentially quantifies the masked variables.
+    fn quantify(
+        &mut self,
+        node: BddNode,
+        mask: &[bool],
+        cache: &mut HashMap<BddNode, BddNode>,
+    ) -> BddNode {
+        let entry = self.nodes[node.index as usize];
+        if entry.var == TERMINAL {
+            return node;
+        } else if let Some(&node) = cache.get(&node) {
+            return node;
+        }
+
+        let low = self.quantify(entry.low, mask, cache);
+        let high = self.quantify(entry.high, mask, cache);
+        let result = if mask[entry.var as usize] {
+            self.apply(BddOp::Or, low, high)
+        } else {
+            self.make(entry.var, low, high)
+        };
+        cache.insert(node, result);
+        result
+    }
+
+    /// Returns the number of satisfying assignments of the variables from
+    /// the level of the node.
+    fn count(&self, node: BddNode, cache: &mut HashMap<BddNode, BigNum>) -> BigNum {
+        if node == ZERO {
+            return BigNum::zero();
+        } else if node == ONE {
+            return BigNum::one();
+        } else if let Some(count) = cache.get(&node) {
+            return count.clone();
+        }
+
+        let entry = self.nodes[node.index as usize];
+        let mut result = BigNum::zero();
+        for child in [entry.low, entry.high] {
+            let count = self.count(child, cache);
+            let skipped = self.level(child) - entry.var - 1;
+            result = &result + &(&count << skipped as usize);
+        }
+        cache.insert(node, result.clone());
+        result
+    }
+}
+
+impl BoolAlg for Bdd {
+    type Elem = BddNode;
+
+    fn bool_lift(&self, elem: bool) -> Self::Elem {
+        if elem {
+            ONE
+        } else {
+            ZERO
+        }
+    }
+
+    fn bool_not(&mut self, elem: Self::Elem) -> Self::Elem {
+        self.apply(BddOp::Xor, elem, ONE)
+    }
+
+    fn bool_or(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        self.apply(BddOp::Or, elem1, elem2)
+    }
+
+    fn bool_xor(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        self.apply(BddOp::Xor, elem1, elem2)
+    }
+
+    fn bool_and(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        self.apply(BddOp::And, elem1, elem2)
+    }
+}
+
+/// An iterator over the models of a node projected onto some variables,
+/// where the variables not occurring in the diagram take both values.
+pub struct BddModels<'a> {
+    bdd: &'a Bdd,
+    order: Vec<(u32, usize)>,
+    stack: Vec<(BddNode, usize, Vec<bool>)>,
+}
+
+impl<'a> Iterator for BddModels<'a> {
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, depth, mut values)) = self.stack.pop() {
+            if node == ZERO {
+                continue;
+            } else if depth == self.order.len() {
+                debug_assert_eq!(node, ONE);
+                return Some(values);
+            }
+
+            // the false branch is explored first
+            let (var, pos) = self.order[depth];
+            let (low, high) = self.bdd.cofactors(node, var);
+            let mut other = values.clone();
+            other[pos] = true;
+            self.stack.push((high, depth + 1, other));
+            values[pos] = false;
+            self.stack.push((low, depth + 1, values));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Shape, Tensor, TensorAlg};
+    use super::*;
+
+    #[test]
+    fn operations() {
+        let mut alg = Bdd::new();
+        let a = alg.add_variable();
+        let b = alg.add_variable();
+        let c = alg.add_variable();
+        let d = alg.bool_and(a, b);
+        let e = alg.bool_or(d, c);
+        let f = alg.bool_and(b, a);
+        assert_eq!(alg.bool_or(c, f), e);
+        assert_eq!(alg.bool_xor(e, e), alg.bool_zero());
+        assert_eq!(alg.sat_count(e), BigNum::from(5));
+        assert_eq!(alg.sat_count(alg.bool_unit()), BigNum::from(8));
+        assert_eq!(alg.sat_count(c), BigNum::from(4));
+
+        assert_eq!(alg.exists(e, &[c]), alg.bool_unit());
+        assert_eq!(alg.forall(e, &[c]), d);
+        assert_eq!(alg.exists(d, &[a]), b);
+        let g = alg.bool_not(a);
+        assert_eq!(alg.forall(d, &[a, b]), alg.bool_zero());
+        assert_eq!(alg.bool_or(g, d), alg.bool_imp(a, b));
+    }
+
+    #[test]
+    fn models() {
+        let mut alg = Bdd::new();
+        let a = alg.add_variable();
+        let b = alg.add_variable();
+        let c = alg.add_variable();
+        let d = alg.bool_xor(a, b);
+        let e = alg.bool_and(d, c);
+
+        // the enumeration follows the order of the variables in the diagram
+        let models: Vec<Vec<bool>> = alg.models(e, &[b, a]).collect();
+        assert_eq!(models, vec![vec![true, false], vec![false, true]]);
+        let models: Vec<Vec<bool>> = alg.models(e, &[c]).collect();
+        assert_eq!(models, vec![vec![true]]);
+        assert_eq!(alg.models(d, &[a, b, c]).count(), 4);
+        assert_eq!(alg.models(alg.bool_zero(), &[a]).count(), 0);
+        assert_eq!(alg.models(alg.bool_unit(), &[]).count(), 1);
+    }
+
+    #[test]
+    fn tensors() {
+        // partial functions from a 4-element set to a 3-element set
+        let mut alg = Bdd::new();
+        let rel: Tensor<BddNode> = Tensor::create(Shape::new(vec![3, 4]), |_| alg.add_variable());
+        let elem = alg.tensor_amo(rel);
+        let elem = alg.tensor_all(elem);
+        assert_eq!(alg.sat_count(elem.scalar()), BigNum::from(256));
+    }
+}
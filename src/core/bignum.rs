@@ -0,0 +1,230 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Arbitrary precision natural numbers for counting models.
+
+use std::{cmp, fmt, ops};
+
+/// A natural number of arbitrary size.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigNum {
+    /// The 32-bit digits in little endian order without leading zeros.
+    digits: Vec<u32>,
+}
+
+impl BigNum {
+    /// Returns the number zero.
+    pub fn zero() -> Self {
+        Default::default()
+    }
+
+    /// Returns the number one.
+    pub fn one() -> Self {
+        BigNum::from(1)
+    }
+
+    /// Returns two raised to the given power.
+    pub fn pow2(exp: usize) -> Self {
+        let mut digits = vec![0; exp / 32 + 1];
+        digits[exp / 32] = 1 << (exp % 32);
+        BigNum { digits }
+    }
+
+    /// Returns true if this number is zero.
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    /// Returns the value of this number if it fits into an `u64`.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.digits.len() {
+            0 => Some(0),
+            1 => Some(self.digits[0] as u64),
+            2 => Some(self.digits[0] as u64 | (self.digits[1] as u64) << 32),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bits needed to represent this number.
+    pub fn bits(&self) -> usize {
+        match self.digits.last() {
+            Some(last) => 32 * self.digits.len() - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn normalize(&mut self) {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+
+    /// Divides this number by the given small number in place and returns
+    /// the remainder.
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut rem: u64 = 0;
+        for digit in self.digits.iter_mut().rev() {
+            let cur = rem << 32 | *digit as u64;
+            *digit = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        self.normalize();
+        rem as u32
+    }
+}
+
+impl From<u64> for BigNum {
+    fn from(value: u64) -> Self {
+        let mut num = BigNum {
+            digits: vec![value as u32, (value >> 32) as u32],
+        };
+        num.normalize();
+        num
+    }
+}
+
+impl<'a> ops::Add<&'a BigNum> for &'a BigNum {
+    type Output = BigNum;
+
+    fn add(self, other: &BigNum) -> BigNum {
+        let len = cmp::max(self.digits.len(), other.digits.len());
+        let mut digits = Vec::with_capacity(len + 1);
+        let mut carry = 0;
+        for i in 0..len {
+            let a = *self.digits.get(i).unwrap_or(&0) as u64;
+            let b = *other.digits.get(i).unwrap_or(&0) as u64;
+            let sum = a + b + carry;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+        let mut num = BigNum { digits };
+        num.normalize();
+        num
+    }
+}
+
+impl<'a> ops::Mul<&'a BigNum> for &'a BigNum {
+    type Output = BigNum;
+
+    fn mul(self, other: &BigNum) -> BigNum {
+        if self.is_zero() || other.is_zero() {
+            return BigNum::zero();
+        }
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, &a) in self.digits.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.digits.iter().enumerate() {
+                let cur = a as u64 * b as u64 + digits[i + j] as u64 + carry;
+                digits[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            digits[i + other.digits.len()] = carry as u32;
+        }
+        let mut num = BigNum { digits };
+        num.normalize();
+        num
+    }
+}
+
+impl ops::Shl<usize> for &BigNum {
+    type Output = BigNum;
+
+    fn shl(self, exp: usize) -> BigNum {
+        if self.is_zero() {
+            return BigNum::zero();
+        }
+        let mut digits = vec![0u32; exp / 32];
+        let shift = exp % 32;
+        let mut carry = 0;
+        for &digit in self.digits.iter() {
+            digits.push(digit << shift | carry);
+            carry = if shift == 0 { 0 } else { digit >> (32 - shift) };
+        }
+        digits.push(carry);
+        let mut num = BigNum { digits };
+        num.normalize();
+        num
+    }
+}
+
+impl PartialOrd for BigNum {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigNum {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl fmt::Display for BigNum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // collect groups of nine decimal digits
+        let mut num = self.clone();
+        let mut groups = Vec::new();
+        while !num.is_zero() {
+            groups.push(num.div_rem_small(1_000_000_000));
+        }
+        write!(f, "{}", groups.pop().unwrap())?;
+        for group in groups.iter().rev() {
+            write!(f, "{:09}", group)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BigNum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let a = BigNum::from(u64::MAX);
+        let b = &a + &BigNum::one();
+        assert_eq!(b, BigNum::pow2(64));
+        assert_eq!(b.to_u64(), None);
+        assert_eq!(b.bits(), 65);
+        assert_eq!(b.to_string(), "18446744073709551616");
+        assert_eq!(&BigNum::one() << 64, b);
+        assert!(a < b);
+
+        let c = &b * &b;
+        assert_eq!(c, BigNum::pow2(128));
+        assert_eq!(c.to_string(), "340282366920938463463374607431768211456");
+        let d = &BigNum::from(1000) * &BigNum::from(1_000_000_007);
+        assert_eq!(d.to_string(), "1000000007000");
+        assert_eq!(d.to_u64(), Some(1_000_000_007_000));
+        assert_eq!(BigNum::zero().to_string(), "0");
+        assert_eq!(&BigNum::from(3) << 31, BigNum::from(3 << 31));
+    }
+}
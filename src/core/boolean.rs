@@ -19,9 +19,17 @@
 //! This can be used to calculate with boolean terms and ask for a model
 //! where a given set of terms are all true.
 
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::iter;
+use std::time::Instant;
 
-use super::{create_solver, GenElem, GenVec, GenVector as _, Literal, SatSolver};
+use super::{
+    add_progress, cardinality, counting, create_solver, cubes, del_progress, pseudo, set_progress,
+    BigNum, CardEncoding, CubeConfig, Formula, GenElem, GenVec, GenVector as _, Interrupt,
+    Interrupted, Limits, Literal, ParModels, PbEncoding, Proof, ProofError, ProofResult, ProofStep,
+    SatSolver, SolveResult, Statistics,
+};
 
 /// A boolean algebra supporting boolean calculation.
 pub trait BoolAlg {
@@ -152,6 +160,118 @@ pub trait BoolAlg {
         self.bool_not(min2)
     }
 
+    /// Returns the number of true elements in unary truncated to the given
+    /// bound, that is, the `i`-th element of the result is true if at least
+    /// `i + 1` of the given elements are true.
+    fn bool_fold_count<ITER>(
+        &mut self,
+        elems: ITER,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Vec<Self::Elem>
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        cardinality::count(self, elems.collect(), bound, encoding)
+    }
+
+    /// Computes the at least `bound` many predicate over the given elements.
+    fn bool_fold_atleast<ITER>(
+        &mut self,
+        elems: ITER,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        match self.bool_fold_count(elems, bound, encoding).pop() {
+            Some(elem) => elem,
+            None => self.bool_unit(),
+        }
+    }
+
+    /// Computes the at most `bound` many predicate over the given elements.
+    fn bool_fold_atmost<ITER>(
+        &mut self,
+        elems: ITER,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let mut counts = self.bool_fold_count(elems, bound + 1, encoding);
+        let over = counts.pop().unwrap();
+        self.bool_not(over)
+    }
+
+    /// Computes the exactly `bound` many predicate over the given elements.
+    fn bool_fold_exactly<ITER>(
+        &mut self,
+        elems: ITER,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let mut counts = self.bool_fold_count(elems, bound + 1, encoding);
+        let over = counts.pop().unwrap();
+        let over = self.bool_not(over);
+        match counts.pop() {
+            Some(elem) => self.bool_and(elem, over),
+            None => over,
+        }
+    }
+
+    /// Computes the predicate that the sum of the weights of the true
+    /// elements is at most the given bound. Weights can be negative.
+    fn bool_fold_pb_atmost<ITER>(
+        &mut self,
+        terms: ITER,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem
+    where
+        ITER: Iterator<Item = (Self::Elem, i64)>,
+    {
+        pseudo::atmost(self, terms.collect(), bound, encoding)
+    }
+
+    /// Computes the predicate that the sum of the weights of the true
+    /// elements is at least the given bound. Weights can be negative.
+    fn bool_fold_pb_atleast<ITER>(
+        &mut self,
+        terms: ITER,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem
+    where
+        ITER: Iterator<Item = (Self::Elem, i64)>,
+    {
+        let terms = terms.map(|(elem, weight)| (elem, -weight)).collect();
+        pseudo::atmost(self, terms, -bound, encoding)
+    }
+
+    /// Computes the predicate that the sum of the weights of the true
+    /// elements is exactly the given bound. Weights can be negative.
+    fn bool_fold_pb_exactly<ITER>(
+        &mut self,
+        terms: ITER,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem
+    where
+        ITER: Iterator<Item = (Self::Elem, i64)>,
+    {
+        let terms: Vec<(Self::Elem, i64)> = terms.collect();
+        let upper = pseudo::atmost(self, terms.clone(), bound, encoding);
+        let terms = terms.into_iter().map(|(elem, weight)| (elem, -weight));
+        let lower = pseudo::atmost(self, terms.collect(), -bound, encoding);
+        self.bool_and(upper, lower)
+    }
+
     /// Returns true if the two sequences are equal.
     fn bool_cmp_equ<ITER>(&mut self, pairs: ITER) -> Self::Elem
     where
@@ -254,27 +374,390 @@ impl BoolAlg for Boolean {
     }
 }
 
-/// The free boolean algebra backed by a SAT solver.
+/// The bit patterns of the first six variables within a word.
+const WORD_PATTERNS: [u64; 6] = [
+    0xaaaa_aaaa_aaaa_aaaa,
+    0xcccc_cccc_cccc_cccc,
+    0xf0f0_f0f0_f0f0_f0f0,
+    0xff00_ff00_ff00_ff00,
+    0xffff_0000_ffff_0000,
+    0xffff_ffff_0000_0000,
+];
+
+/// The boolean algebra of `u64` words evaluating 64 assignments at once,
+/// one in each bit. The assignments of the variables are numbered so that
+/// bit `i` of the number is the value of variable `i`. The first six
+/// variables change within a word, while the rest are fixed by the batch
+/// of 64 consecutive assignments selected with `set_batch`.
+#[derive(Default, Debug)]
+pub struct BitParallel {
+    num_vars: usize,
+    batch: usize,
+}
+
+impl BitParallel {
+    /// Creates a new algebra for the given number of variables.
+    pub fn new(num_vars: usize) -> Self {
+        assert!(num_vars < 64 + 6);
+        BitParallel { num_vars, batch: 0 }
+    }
+
+    /// Returns the number of batches needed to cover all assignments.
+    pub fn num_batches(&self) -> usize {
+        1 << self.num_vars.saturating_sub(6)
+    }
+
+    /// Selects the batch of assignments evaluated by the words.
+    pub fn set_batch(&mut self, batch: usize) {
+        assert!(batch < self.num_batches());
+        self.batch = batch;
+    }
+
+    /// Returns the word of the given variable in the current batch.
+    pub fn variable(&self, var: usize) -> u64 {
+        assert!(var < self.num_vars);
+        if var < 6 {
+            WORD_PATTERNS[var]
+        } else if (self.batch >> (var - 6)) & 1 != 0 {
+            u64::MAX
+        } else {
+            0
+        }
+    }
+
+    /// Returns the word whose bits mark the valid assignments, which are
+    /// all of them unless there are less than six variables.
+    pub fn valid(&self) -> u64 {
+        if self.num_vars >= 6 {
+            u64::MAX
+        } else {
+            u64::MAX >> (64 - (1 << self.num_vars))
+        }
+    }
+
+    /// Returns the number of assignments of the variables where the
+    /// predicate is true. The predicate is evaluated once in each batch
+    /// with the words of the variables.
+    pub fn count_models<PRED>(num_vars: usize, mut pred: PRED) -> u64
+    where
+        PRED: FnMut(&mut BitParallel, &[u64]) -> u64,
+    {
+        let mut alg = BitParallel::new(num_vars);
+        let mut count = 0;
+        for batch in 0..alg.num_batches() {
+            alg.set_batch(batch);
+            let vars: Vec<u64> = (0..num_vars).map(|var| alg.variable(var)).collect();
+            count += (pred(&mut alg, &vars) & alg.valid()).count_ones() as u64;
+        }
+        count
+    }
+}
+
+impl BoolAlg for BitParallel {
+    type Elem = u64;
+
+    fn bool_lift(&self, elem: bool) -> Self::Elem {
+        if elem {
+            u64::MAX
+        } else {
+            0
+        }
+    }
+
+    fn bool_not(&mut self, elem: Self::Elem) -> Self::Elem {
+        !elem
+    }
+
+    fn bool_or(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        elem1 | elem2
+    }
+
+    fn bool_xor(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        elem1 ^ elem2
+    }
+
+    fn bool_and(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        elem1 & elem2
+    }
+
+    fn bool_equ(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        !(elem1 ^ elem2)
+    }
+
+    fn bool_imp(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        !elem1 | elem2
+    }
+}
+
+/// The free boolean algebra backed by a SAT solver. The gates are hashed
+/// structurally by default, so building the same gate over the same inputs
+/// again returns the earlier output literal without new clauses.
 #[derive(Debug)]
 pub struct Solver {
     solver: Box<dyn SatSolver>,
     unit: Literal,
     zero: Literal,
+    scopes: Vec<Literal>,
+    total: Statistics,
+    last: Statistics,
+    publish: bool,
+    hashing: bool,
+    or_gates: HashMap<(Literal, Literal), Literal>,
+    xor_gates: HashMap<(Literal, Literal), Literal>,
+    gates_created: u64,
+    gates_reused: u64,
 }
 
 impl Solver {
     /// Creates a new free boolean algebra.
     pub fn new(solver_name: &str) -> Self {
-        let mut solver = create_solver(solver_name);
+        Solver::with_solver(create_solver(solver_name))
+    }
+
+    /// Creates a new free boolean algebra backed by the given SAT solver.
+    pub fn with_solver(mut solver: Box<dyn SatSolver>) -> Self {
         let unit = solver.add_variable();
         let zero = solver.negate(unit);
         solver.add_clause(&[unit]);
-        Solver { solver, unit, zero }
+        Solver {
+            solver,
+            unit,
+            zero,
+            scopes: Vec::new(),
+            total: Default::default(),
+            last: Default::default(),
+            publish: false,
+            hashing: true,
+            or_gates: HashMap::new(),
+            xor_gates: HashMap::new(),
+            gates_created: 0,
+            gates_reused: 0,
+        }
+    }
+
+    /// Creates a new free boolean algebra that records the formula and logs
+    /// a proof of unsatisfiability, see `prove_unsat`. Returns `None` if the
+    /// given solver does not support proof logging (use "batsat" or
+    /// "varisat"). CaDiCaL does not support it, so the empty name works
+    /// only if the "batsat" feature is enabled.
+    pub fn with_proof(solver_name: &str) -> Option<Self> {
+        let mut solver = create_solver(&format!("record:{}", solver_name));
+        if solver.enable_proof() {
+            Some(Solver::with_solver(solver))
+        } else {
+            None
+        }
     }
 
     pub fn get_name(&self) -> &'static str {
         self.solver.get_name()
     }
+
+    /// Returns the formula built so far if the underlying SAT solver keeps
+    /// track of it (use the "record:" prefix for the solver name). This can
+    /// be used to export the problem in the DIMACS format.
+    pub fn get_formula(&self) -> Option<&Formula> {
+        self.solver.get_formula()
+    }
+
+    /// Returns a handle that can be sent to another thread to interrupt the
+    /// running search, if the underlying SAT solver supports it.
+    pub fn interrupt_handle(&self) -> Option<Interrupt> {
+        self.solver.interrupt_handle()
+    }
+
+    /// Runs the solver without assumptions and returns a DRAT proof that
+    /// the formula built so far is unsatisfiable, if it is. The proof is
+    /// verified against the recorded formula before it is returned, and an
+    /// error is returned if the check fails. The solver must be created
+    /// with `with_proof`, which needs the "batsat" or "varisat" feature,
+    /// since CaDiCaL cannot log proofs. VariSat cannot log the clauses
+    /// added after its first call, so an error is returned in that case.
+    pub fn prove_unsat(&mut self) -> Result<ProofResult, ProofError> {
+        assert!(self.scopes.is_empty(), "scopes are not supported");
+        match self.solve_limited(&[], &Default::default()) {
+            SolveResult::Sat => return Ok(ProofResult::Sat),
+            SolveResult::Unknown => return Ok(ProofResult::Unknown),
+            SolveResult::Unsat => {}
+        }
+
+        let steps = self.solver.proof_steps().ok_or_else(|| ProofError {
+            step: 0,
+            msg: "the solver could not log the proof".into(),
+        })?;
+        let mut proof = Proof::new();
+        for step in steps {
+            proof.add_step(step.map(Formula::encode));
+        }
+        if proof.steps().last() != Some(&ProofStep::Add(Vec::new())) {
+            proof.add_step(ProofStep::Add(Vec::new()));
+        }
+
+        let formula = self.solver.get_formula().expect("formula is not recorded");
+        proof.check(formula)?;
+        Ok(ProofResult::Unsat(proof))
+    }
+
+    /// Opens a new scope. The clauses added with `bool_add_clause` until
+    /// the matching `pop` are guarded by a fresh activation literal, which
+    /// is assumed in every solver call while the scope is open. The gates
+    /// of the algebra are not scoped, so the elements remain valid.
+    pub fn push(&mut self) {
+        let active = self.solver.add_variable();
+        self.scopes.push(active);
+    }
+
+    /// Closes the innermost scope and retracts the clauses added within it
+    /// by permanently disabling its activation literal. The clauses learnt
+    /// by the solver remain valid, since they depend on the activation
+    /// literal as well. Panics if there is no open scope.
+    pub fn pop(&mut self) {
+        let active = self.scopes.pop().expect("no open scope");
+        let inactive = self.solver.negate(active);
+        self.solver.add_clause(&[inactive]);
+    }
+
+    /// Returns the number of open scopes.
+    pub fn num_scopes(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Returns the statistics of all solver calls since the creation of
+    /// this algebra.
+    pub fn statistics(&self) -> Statistics {
+        Statistics {
+            calls: self.total.calls,
+            time: self.total.time,
+            ..self.solver.statistics()
+        }
+    }
+
+    /// Returns the statistics of the last solver call.
+    pub fn last_statistics(&self) -> Statistics {
+        self.last
+    }
+
+    /// Starts or stops collecting the statistics of the SAT solver that
+    /// slow down the search, such as the number of learnt clauses of
+    /// CaDiCaL.
+    pub fn collect_statistics(&mut self, enable: bool) {
+        self.solver.collect_statistics(enable);
+    }
+
+    /// Starts or stops publishing the statistics into the `sat_calls`,
+    /// `sat_time` (in seconds) and `sat_learnt` progress variables. The
+    /// number of learnt clauses is updated during the search if the SAT
+    /// solver supports it, otherwise after each call.
+    pub fn publish_progress(&mut self, enable: bool) {
+        if enable == self.publish {
+            return;
+        }
+        self.publish = enable;
+        self.solver.publish_progress(enable);
+        for name in ["sat_calls", "sat_time", "sat_learnt"] {
+            if enable {
+                add_progress(name);
+            } else {
+                del_progress(name);
+            }
+        }
+        if enable {
+            self.set_progress();
+        }
+    }
+
+    /// Updates the published progress variables.
+    fn set_progress(&self) {
+        let stats = self.statistics();
+        set_progress("sat_calls", stats.calls);
+        set_progress("sat_time", stats.time.as_secs());
+        if let Some(learnt) = stats.learnt_clauses {
+            set_progress("sat_learnt", learnt);
+        }
+    }
+
+    /// Runs the SAT solver and updates the statistics.
+    fn solve_limited(&mut self, assumptions: &[Literal], limits: &Limits) -> SolveResult {
+        let before = self.statistics();
+        let start = Instant::now();
+        let result = self.solver.solve_limited(assumptions, limits);
+        self.total.calls += 1;
+        self.total.time += start.elapsed();
+        self.last = self.statistics().since(&before);
+        if self.publish {
+            self.set_progress();
+        }
+        result
+    }
+
+    /// Turns the structural hashing of gates on or off. The gates built
+    /// while hashing was on are still remembered when it is turned on again.
+    pub fn set_gate_hashing(&mut self, enable: bool) {
+        self.hashing = enable;
+    }
+
+    /// Returns the number of gates created with fresh variables.
+    pub fn num_gates_created(&self) -> u64 {
+        self.gates_created
+    }
+
+    /// Returns the number of gates found in the hash table instead of
+    /// being created again.
+    pub fn num_gates_reused(&self) -> u64 {
+        self.gates_reused
+    }
+
+    /// Returns the positive literal of the variable of the given literal,
+    /// which is the one with the smaller value, and whether the given
+    /// literal was negated.
+    fn canonical(&self, elem: Literal) -> (Literal, bool) {
+        let not_elem = self.solver.negate(elem);
+        if not_elem.value < elem.value {
+            (not_elem, true)
+        } else {
+            (elem, false)
+        }
+    }
+
+    /// Returns the output of the gate with the given key from the hash
+    /// table, or creates it with the given function and remembers it.
+    fn hashed_gate<F>(&mut self, xor: bool, key: (Literal, Literal), create: F) -> Literal
+    where
+        F: FnOnce(&mut dyn SatSolver) -> Literal,
+    {
+        let gates = if xor {
+            &mut self.xor_gates
+        } else {
+            &mut self.or_gates
+        };
+        if self.hashing {
+            if let Some(&elem) = gates.get(&key) {
+                self.gates_reused += 1;
+                return elem;
+            }
+        }
+        let elem = create(self.solver.as_mut());
+        self.gates_created += 1;
+        if self.hashing {
+            gates.insert(key, elem);
+        }
+        elem
+    }
+
+    /// Returns the recorded formula where the clauses of the open scopes
+    /// are enabled by unit clauses.
+    fn scoped_formula(&self) -> Cow<'_, Formula> {
+        let formula = self.solver.get_formula().expect("formula is not recorded");
+        if self.scopes.is_empty() {
+            Cow::Borrowed(formula)
+        } else {
+            let mut formula = formula.clone();
+            for &active in self.scopes.iter() {
+                formula.add_clause(&[active]);
+            }
+            Cow::Owned(formula)
+        }
+    }
 }
 
 impl BoolAlg for Solver {
@@ -301,13 +784,20 @@ impl BoolAlg for Solver {
         } else if elem2 == self.zero {
             elem1
         } else {
-            let not_elem1 = self.solver.negate(elem1);
-            let elem3 = self.solver.add_variable();
-            let not_elem3 = self.solver.negate(elem3);
-            self.solver.add_clause(&[not_elem1, elem3]);
-            self.solver.add_clause(&[not_elem2, elem3]);
-            self.solver.add_clause(&[elem1, elem2, not_elem3]);
-            elem3
+            let key = if elem1.value <= elem2.value {
+                (elem1, elem2)
+            } else {
+                (elem2, elem1)
+            };
+            self.hashed_gate(false, key, |solver| {
+                let not_elem1 = solver.negate(elem1);
+                let elem3 = solver.add_variable();
+                let not_elem3 = solver.negate(elem3);
+                solver.add_clause(&[not_elem1, elem3]);
+                solver.add_clause(&[not_elem2, elem3]);
+                solver.add_clause(&[elem1, elem2, not_elem3]);
+                elem3
+            })
         }
     }
 
@@ -326,9 +816,24 @@ impl BoolAlg for Solver {
         } else if elem1 == not_elem2 {
             self.unit
         } else {
-            let elem3 = self.solver.add_variable();
-            self.solver.add_xor_clause(elem1, elem2, elem3);
-            elem3
+            // the negations are moved to the output
+            let (elem1, neg1) = self.canonical(elem1);
+            let (elem2, neg2) = self.canonical(elem2);
+            let key = if elem1.value <= elem2.value {
+                (elem1, elem2)
+            } else {
+                (elem2, elem1)
+            };
+            let elem3 = self.hashed_gate(true, key, |solver| {
+                let elem3 = solver.add_variable();
+                solver.add_xor_clause(elem1, elem2, elem3);
+                elem3
+            });
+            if neg1 != neg2 {
+                self.solver.negate(elem3)
+            } else {
+                elem3
+            }
         }
     }
 }
@@ -351,6 +856,74 @@ where
         assumptions: &[Self::Elem],
         literals: ITER,
     ) -> Option<GenVec<bool>>
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        self.bool_find_one_model_limited(assumptions, literals, &Default::default())
+            .expect("unlimited solver was interrupted")
+    }
+
+    /// Runs the solver with the given assumptions and resource limits, and
+    /// returns the value of the given literals if a solution is found. An
+    /// error is returned if the solver ran out of resources.
+    fn bool_find_one_model_limited<ITER>(
+        &mut self,
+        assumptions: &[Self::Elem],
+        literals: ITER,
+        limits: &Limits,
+    ) -> Result<Option<GenVec<bool>>, Interrupted>
+    where
+        ITER: Iterator<Item = Self::Elem>;
+
+    /// Returns a mask telling which of the given assumptions were used to
+    /// derive the unsatisfiability in the last call of the solver. This
+    /// should be called only when no model was found.
+    fn bool_failed_assumptions<ITER>(&self, assumptions: ITER) -> GenVec<bool>
+    where
+        ITER: Iterator<Item = Self::Elem>;
+
+    /// Adds a clause to the solver that excludes the given values of the
+    /// given literals.
+    fn bool_add_blocking_clause<ITER>(&mut self, pairs: ITER)
+    where
+        ITER: Iterator<Item = (Self::Elem, bool)>,
+    {
+        let clause: Vec<Self::Elem> = pairs
+            .map(|(l, b)| self.bool_xor(self.bool_lift(b), l))
+            .collect();
+        self.bool_add_clause(&clause);
+    }
+
+    /// Returns the exact number of models with respect to the given
+    /// literals without enumerating them. This works on the recorded
+    /// formula and ignores the assumptions of earlier calls.
+    fn bool_count_models<ITER>(&self, literals: ITER) -> BigNum
+    where
+        ITER: Iterator<Item = Self::Elem>;
+
+    /// Returns the number of models with respect to the given literals by
+    /// solving the cubes over the split literals in parallel threads, each
+    /// with its own solver. The split literals must be among the given
+    /// ones, and they are chosen automatically if none are given. This
+    /// works on the recorded formula and ignores the assumptions of earlier
+    /// calls.
+    fn bool_par_find_num_models<ITER>(
+        &self,
+        literals: ITER,
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> usize
+    where
+        ITER: Iterator<Item = Self::Elem>;
+
+    /// Returns an iterator over the models with respect to the given
+    /// literals found by parallel threads, see `bool_par_find_num_models`.
+    fn bool_par_models<ITER>(
+        &self,
+        literals: ITER,
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> ParModels
     where
         ITER: Iterator<Item = Self::Elem>;
 
@@ -361,17 +934,9 @@ where
     {
         let mut count = 0;
         let literals: GenVec<Self::Elem> = literals.collect();
-        let mut clause: Vec<Self::Elem> = Vec::with_capacity(literals.len());
         while let Some(result) = self.bool_find_one_model(&[], literals.iter()) {
             count += 1;
-            clause.clear();
-            clause.extend(
-                literals
-                    .iter()
-                    .zip(result.into_iter())
-                    .map(|(l, b)| self.bool_xor(self.bool_lift(b), l)),
-            );
-            self.bool_add_clause(&clause);
+            self.bool_add_blocking_clause(literals.iter().zip(result.into_iter()));
         }
         count
     }
@@ -452,27 +1017,94 @@ impl BoolSat for Solver {
     }
 
     fn bool_add_clause(&mut self, clause: &[Self::Elem]) {
-        self.solver.add_clause(clause)
+        if let Some(&active) = self.scopes.last() {
+            let mut clause = clause.to_vec();
+            clause.push(self.solver.negate(active));
+            self.solver.add_clause(&clause);
+        } else {
+            self.solver.add_clause(clause);
+        }
     }
 
-    fn bool_find_one_model<ITER>(
+    fn bool_find_one_model_limited<ITER>(
         &mut self,
         assumptions: &[Self::Elem],
         literals: ITER,
-    ) -> Option<GenVec<bool>>
+        limits: &Limits,
+    ) -> Result<Option<GenVec<bool>>, Interrupted>
     where
         ITER: Iterator<Item = Self::Elem>,
     {
-        if self.solver.solve_with(assumptions) {
-            Some(literals.map(|e| self.solver.get_value(e)).collect())
+        let result = if self.scopes.is_empty() {
+            self.solve_limited(assumptions, limits)
         } else {
-            None
+            let mut scoped = self.scopes.clone();
+            scoped.extend_from_slice(assumptions);
+            self.solve_limited(&scoped, limits)
+        };
+        match result {
+            SolveResult::Sat => Ok(Some(literals.map(|e| self.solver.get_value(e)).collect())),
+            SolveResult::Unsat => Ok(None),
+            SolveResult::Unknown => Err(Interrupted),
         }
     }
+
+    fn bool_failed_assumptions<ITER>(&self, assumptions: ITER) -> GenVec<bool>
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let failed: HashSet<Literal> = self.solver.failed_assumptions().into_iter().collect();
+        assumptions.map(|e| failed.contains(&e)).collect()
+    }
+
+    /// Panics if the formula is not recorded, so the solver must be created
+    /// with the "record:" prefix.
+    fn bool_count_models<ITER>(&self, literals: ITER) -> BigNum
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let projection: Vec<i32> = literals.map(Formula::encode).collect();
+        counting::count_models(&self.scoped_formula(), &projection)
+    }
+
+    /// Panics if the formula is not recorded, so the solver must be created
+    /// with the "record:" prefix.
+    fn bool_par_find_num_models<ITER>(
+        &self,
+        literals: ITER,
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> usize
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let formula = self.scoped_formula().into_owned();
+        let projection = literals.map(Formula::encode).collect();
+        let split = split.iter().copied().map(Formula::encode).collect();
+        cubes::count_models(formula, projection, split, config)
+    }
+
+    /// Panics if the formula is not recorded, so the solver must be created
+    /// with the "record:" prefix.
+    fn bool_par_models<ITER>(
+        &self,
+        literals: ITER,
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> ParModels
+    where
+        ITER: Iterator<Item = Self::Elem>,
+    {
+        let formula = self.scoped_formula().into_owned();
+        let projection = literals.map(Formula::encode).collect();
+        let split = split.iter().copied().map(Formula::encode).collect();
+        cubes::models(formula, projection, split, config)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::{Shape, Tensor, TensorAlg};
     use super::*;
 
     #[test]
@@ -498,4 +1130,187 @@ mod tests {
         assert_eq!(s.get(0), true);
         assert_eq!(s.get(1), true);
     }
+
+    #[test]
+    fn bit_parallel() {
+        // the words agree with the assignments of the boolean algebra
+        let mut alg = BitParallel::new(8);
+        alg.set_batch(3);
+        let vars: Vec<u64> = (0..8).map(|var| alg.variable(var)).collect();
+        let a = alg.bool_fold_atmost(vars.iter().copied(), 5, CardEncoding::Totalizer);
+        let b = alg.bool_maj(vars[0], vars[6], vars[7]);
+        for bit in 0..64 {
+            let number = 3 * 64 + bit;
+            let values: Vec<bool> = (0..8).map(|var| (number >> var) & 1 != 0).collect();
+            let mut boolean = Boolean();
+            let c = boolean.bool_fold_atmost(values.iter().copied(), 5, CardEncoding::Totalizer);
+            let d = boolean.bool_maj(values[0], values[6], values[7]);
+            assert_eq!((a >> bit) & 1 != 0, c);
+            assert_eq!((b >> bit) & 1 != 0, d);
+        }
+
+        // few variables use only some bits of the word
+        let count = BitParallel::count_models(3, |alg, vars| alg.bool_or(vars[0], vars[2]));
+        assert_eq!(count, 6);
+
+        // partial functions from a 4-element set to a 3-element set
+        let count = BitParallel::count_models(12, |alg, vars| {
+            let mut iter = vars.iter().copied();
+            let rel = Tensor::create(Shape::new(vec![3, 4]), |_| iter.next().unwrap());
+            let elem = alg.tensor_amo(rel);
+            alg.tensor_all(elem).scalar()
+        });
+        assert_eq!(count, 256);
+    }
+
+    #[test]
+    fn scopes() {
+        let mut alg = Solver::new("");
+        let a = alg.bool_add_variable();
+        let b = alg.bool_add_variable();
+        let c = alg.bool_or(a, b);
+        alg.bool_add_clause(&[c]);
+
+        alg.push();
+        let d = alg.bool_not(a);
+        alg.bool_add_clause(&[d]);
+        assert_eq!(alg.num_scopes(), 1);
+        let s = alg
+            .bool_find_one_model(&[], [a, b].iter().copied())
+            .unwrap();
+        assert!(!s.get(0) && s.get(1));
+        alg.push();
+        let e = alg.bool_not(b);
+        alg.bool_add_clause(&[e]);
+        assert!(alg
+            .bool_find_one_model(&[], [a, b].iter().copied())
+            .is_none());
+        alg.pop();
+        assert!(alg
+            .bool_find_one_model(&[], [a, b].iter().copied())
+            .is_some());
+        alg.pop();
+
+        assert_eq!(alg.num_scopes(), 0);
+        assert_eq!(alg.bool_find_num_models_method1([a, b].iter().copied()), 3);
+    }
+
+    #[test]
+    fn statistics() {
+        let mut alg = Solver::new("");
+        alg.publish_progress(true);
+        let elems: Vec<Literal> = (0..3).map(|_| alg.bool_add_variable()).collect();
+        while let Some(values) = alg.bool_find_one_model(&[], elems.iter().copied()) {
+            alg.bool_add_blocking_clause(elems.iter().copied().zip(values.iter()));
+        }
+        let stats = alg.statistics();
+        assert_eq!(stats.calls, 9);
+        assert_eq!(alg.last_statistics().calls, 1);
+        assert!(stats.time >= alg.last_statistics().time);
+        assert!(stats.to_string().starts_with("calls=9, time="));
+        alg.publish_progress(false);
+    }
+
+    #[test]
+    fn gate_hashing() {
+        let mut alg = Solver::new("");
+        let a = alg.bool_add_variable();
+        let b = alg.bool_add_variable();
+        let c = alg.bool_and(a, b);
+        let d = alg.bool_and(b, a);
+        assert_eq!(c, d);
+        let not_a = alg.bool_not(a);
+        let not_b = alg.bool_not(b);
+        let e = alg.bool_or(not_a, not_b);
+        assert_eq!(e, alg.bool_not(c));
+
+        let f = alg.bool_xor(a, b);
+        let g = alg.bool_xor(not_b, a);
+        assert_eq!(g, alg.bool_not(f));
+        let h = alg.bool_equ(not_a, not_b);
+        assert_eq!(h, g);
+        assert_eq!(alg.num_gates_created(), 2);
+        assert_eq!(alg.num_gates_reused(), 4);
+
+        alg.set_gate_hashing(false);
+        let i = alg.bool_and(a, b);
+        assert_ne!(i, c);
+        assert_eq!(alg.num_gates_created(), 3);
+        assert_eq!(alg.num_gates_reused(), 4);
+        let j = alg.bool_xor(i, c);
+        assert!(alg.bool_find_one_model(&[j], iter::empty()).is_none());
+    }
+
+    #[test]
+    fn scoped_counting() {
+        let mut alg = Solver::new("record:");
+        let elems: Vec<Literal> = (0..4).map(|_| alg.bool_add_variable()).collect();
+        alg.push();
+        alg.bool_add_clause(&elems[..2]);
+        assert_eq!(
+            alg.bool_count_models(elems.iter().copied()),
+            BigNum::from(12)
+        );
+        alg.pop();
+        assert_eq!(
+            alg.bool_count_models(elems.iter().copied()),
+            BigNum::from(16)
+        );
+    }
+
+    #[cfg(any(feature = "batsat", feature = "varisat"))]
+    fn prove_pigeonhole(solver_name: &str, incremental: bool) {
+        let mut alg = Solver::with_proof(solver_name).unwrap();
+        let holes = 4;
+        let vars: Vec<Vec<Literal>> = (0..=holes)
+            .map(|_| (0..holes).map(|_| alg.bool_add_variable()).collect())
+            .collect();
+        for pigeon in vars.iter() {
+            alg.bool_add_clause(pigeon);
+        }
+        if incremental {
+            assert_eq!(alg.prove_unsat(), Ok(ProofResult::Sat));
+        }
+
+        for hole in 0..holes {
+            let column = vars.iter().map(|pigeon| pigeon[hole]);
+            let amo = alg.bool_fold_amo(column);
+            alg.bool_add_clause(&[amo]);
+        }
+        let proof = match alg.prove_unsat() {
+            Ok(ProofResult::Unsat(proof)) => proof,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert!(!proof.steps().is_empty());
+
+        let mut out: Vec<u8> = Vec::new();
+        proof.write_drat(&mut out).unwrap();
+        assert_eq!(Proof::read_drat(&mut out.as_slice()), Ok(proof));
+    }
+
+    #[test]
+    fn proof() {
+        #[cfg(feature = "cadical")]
+        assert!(Solver::with_proof("cadical").is_none());
+        #[cfg(all(feature = "cadical", not(feature = "batsat")))]
+        assert!(Solver::with_proof("").is_none());
+        #[cfg(feature = "batsat")]
+        assert!(Solver::with_proof("").is_some());
+        #[cfg(feature = "batsat")]
+        prove_pigeonhole("batsat", true);
+        #[cfg(feature = "varisat")]
+        prove_pigeonhole("varisat", false);
+
+        // clauses added after a call of VariSat are not logged
+        #[cfg(feature = "varisat")]
+        {
+            let mut alg = Solver::with_proof("varisat").unwrap();
+            let a = alg.bool_add_variable();
+            alg.bool_add_clause(&[a]);
+            assert_eq!(alg.prove_unsat(), Ok(ProofResult::Sat));
+            let b = alg.bool_not(a);
+            alg.bool_add_clause(&[b]);
+            assert_eq!(alg.prove_unsat().unwrap_err().step, 0);
+        }
+    }
 }
@@ -0,0 +1,181 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Encodings of cardinality constraints with boolean gates.
+
+use super::BoolAlg;
+
+/// The circuit used to count the number of true elements.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CardEncoding {
+    /// Sequential counter with roughly `n * k` gates.
+    Counter,
+    /// Totalizer merging unary counts along a balanced binary tree.
+    #[default]
+    Totalizer,
+    /// Odd-even merge sorting network of Batcher.
+    Sorter,
+}
+
+/// Returns the unary representation of the number of true elements
+/// truncated to the given bound, that is the `i`-th element of the result
+/// is true if at least `i + 1` of the given elements are true.
+pub(super) fn count<ALG>(
+    alg: &mut ALG,
+    elems: Vec<ALG::Elem>,
+    bound: usize,
+    encoding: CardEncoding,
+) -> Vec<ALG::Elem>
+where
+    ALG: BoolAlg + ?Sized,
+{
+    let mut result = match encoding {
+        CardEncoding::Counter => counter(alg, elems, bound),
+        CardEncoding::Totalizer => totalizer(alg, elems, bound),
+        CardEncoding::Sorter => sorter(alg, elems, bound),
+    };
+    let zero = alg.bool_zero();
+    result.resize(bound, zero);
+    result
+}
+
+fn counter<ALG>(alg: &mut ALG, elems: Vec<ALG::Elem>, bound: usize) -> Vec<ALG::Elem>
+where
+    ALG: BoolAlg + ?Sized,
+{
+    let mut sums: Vec<ALG::Elem> = Vec::with_capacity(bound);
+    for elem in elems {
+        let len = sums.len();
+        if len < bound {
+            let tmp = match sums.last() {
+                Some(last) => alg.bool_and(last.clone(), elem.clone()),
+                None => elem.clone(),
+            };
+            sums.push(tmp);
+        }
+        for i in (1..len).rev() {
+            let tmp = alg.bool_and(sums[i - 1].clone(), elem.clone());
+            sums[i] = alg.bool_or(sums[i].clone(), tmp);
+        }
+        if len > 0 {
+            sums[0] = alg.bool_or(sums[0].clone(), elem);
+        }
+    }
+    sums
+}
+
+fn totalizer<ALG>(alg: &mut ALG, mut elems: Vec<ALG::Elem>, bound: usize) -> Vec<ALG::Elem>
+where
+    ALG: BoolAlg + ?Sized,
+{
+    if elems.len() <= 1 || bound == 0 {
+        elems.truncate(bound);
+        return elems;
+    }
+
+    let other = elems.split_off(elems.len() / 2);
+    let left = totalizer(alg, elems, bound);
+    let right = totalizer(alg, other, bound);
+
+    // at least m + 1 elements are true if i of them are on the left
+    // and m + 1 - i of them are on the right
+    let len = (left.len() + right.len()).min(bound);
+    let mut sums = Vec::with_capacity(len);
+    for m in 0..len {
+        let mut sum = alg.bool_zero();
+        for i in 0..=m + 1 {
+            let a = if i == 0 {
+                alg.bool_unit()
+            } else if i <= left.len() {
+                left[i - 1].clone()
+            } else {
+                continue;
+            };
+            let b = if i == m + 1 {
+                alg.bool_unit()
+            } else if m - i < right.len() {
+                right[m - i].clone()
+            } else {
+                continue;
+            };
+            let tmp = alg.bool_and(a, b);
+            sum = alg.bool_or(sum, tmp);
+        }
+        sums.push(sum);
+    }
+    sums
+}
+
+fn sorter<ALG>(alg: &mut ALG, mut elems: Vec<ALG::Elem>, bound: usize) -> Vec<ALG::Elem>
+where
+    ALG: BoolAlg + ?Sized,
+{
+    let len = elems.len().next_power_of_two();
+    let zero = alg.bool_zero();
+    elems.resize(len, zero);
+
+    // comparators move the true elements to the front
+    let mut p = 1;
+    while p < len {
+        let mut k = p;
+        while k >= 1 {
+            for j in (k % p..len - k).step_by(2 * k) {
+                for i in 0..k.min(len - j - k) {
+                    if (i + j) / (2 * p) == (i + j + k) / (2 * p) {
+                        let a = elems[i + j].clone();
+                        let b = elems[i + j + k].clone();
+                        elems[i + j] = alg.bool_or(a.clone(), b.clone());
+                        elems[i + j + k] = alg.bool_and(a, b);
+                    }
+                }
+            }
+            k /= 2;
+        }
+        p *= 2;
+    }
+
+    elems.truncate(bound);
+    elems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Boolean;
+    use super::*;
+
+    #[test]
+    fn count() {
+        let mut alg = Boolean();
+        for encoding in [
+            CardEncoding::Counter,
+            CardEncoding::Totalizer,
+            CardEncoding::Sorter,
+        ] {
+            for len in 0..7 {
+                for bits in 0..(1 << len) {
+                    let elems: Vec<bool> = (0..len).map(|i| (bits >> i) & 1 != 0).collect();
+                    let num = elems.iter().filter(|&&b| b).count();
+                    for bound in 0..len + 2 {
+                        let sums = super::count(&mut alg, elems.clone(), bound, encoding);
+                        let expected: Vec<bool> = (0..bound).map(|i| i < num).collect();
+                        assert_eq!(sums, expected);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,477 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Exact projected model counting with component caching.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::{BigNum, Formula};
+
+/// Returns the number of assignments of the projection variables that can
+/// be extended to a model of the formula. The projection is given by
+/// DIMACS literals, their signs and repetitions are ignored.
+pub(super) fn count_models(formula: &Formula, projection: &[i32]) -> BigNum {
+    let mut clauses: Vec<Vec<i32>> = Vec::new();
+    for clause in formula.clauses() {
+        if let Some(clause) = normalize(clause) {
+            clauses.push(clause);
+        }
+    }
+    let mut num_vars = formula.num_variables();
+    for xor in formula.xor_clauses() {
+        Formula::expand_xor_clause(xor, &mut num_vars, |clause| {
+            if let Some(clause) = normalize(clause) {
+                clauses.push(clause);
+            }
+        });
+    }
+
+    let num_vars = num_vars as usize;
+    let mut counter = Counter {
+        projected: vec![false; num_vars + 1],
+        values: vec![0; num_vars + 1],
+        cache: HashMap::new(),
+    };
+    for lit in projection {
+        counter.projected[lit.unsigned_abs() as usize] = true;
+    }
+    let total = counter.projected.iter().filter(|&&b| b).count();
+
+    counter.count(clauses, total)
+}
+
+/// Sorts the literals of the clause and removes the duplicates, or returns
+/// `None` if the clause is a tautology.
+fn normalize(clause: &[i32]) -> Option<Vec<i32>> {
+    let mut clause = clause.to_vec();
+    clause.sort_unstable_by_key(|lit| (lit.abs(), *lit));
+    clause.dedup();
+    if clause.windows(2).any(|w| w[0] == -w[1]) {
+        None
+    } else {
+        Some(clause)
+    }
+}
+
+/// A DPLL style counter that branches on the projected variables first,
+/// splits the residual formula into independent components and caches the
+/// count of each component. Once a component has no projected variables
+/// left only its satisfiability matters, so its count is zero or one. The
+/// search uses an explicit stack, and the cache is keyed by the clauses of
+/// the sorted component flattened into a single zero separated slice.
+struct Counter {
+    projected: Vec<bool>,
+    values: Vec<i8>,
+    cache: HashMap<Box<[i32]>, BigNum>,
+}
+
+/// A pending step of the search of the counter.
+enum Frame {
+    /// The product of the free assignments and the counts of the
+    /// components so far, and the components still to be counted.
+    Product {
+        result: BigNum,
+        components: Vec<Vec<Vec<i32>>>,
+    },
+    /// The sum of the counts of the branches of a component so far, and
+    /// the literals of the branches still to be counted.
+    Branch {
+        key: Box<[i32]>,
+        component: Vec<Vec<i32>>,
+        lits: Vec<i32>,
+        projected: bool,
+        scope: usize,
+        result: BigNum,
+    },
+}
+
+impl Counter {
+    /// Returns the number of distinct projected variables in the clauses.
+    fn num_projected(&mut self, clauses: &[Vec<i32>]) -> usize {
+        let mut count = 0;
+        for clause in clauses {
+            for &lit in clause {
+                let var = lit.unsigned_abs() as usize;
+                if self.projected[var] && self.values[var] == 0 {
+                    self.values[var] = 2;
+                    count += 1;
+                }
+            }
+        }
+        for clause in clauses {
+            for &lit in clause {
+                self.values[lit.unsigned_abs() as usize] = 0;
+            }
+        }
+        count
+    }
+
+    /// Removes the satisfied clauses and the false literals with respect
+    /// to the given literals, or returns `None` if a clause became empty.
+    fn assign(&mut self, clauses: &[Vec<i32>], lits: &[i32]) -> Option<Vec<Vec<i32>>> {
+        for &lit in lits {
+            self.values[lit.unsigned_abs() as usize] = lit.signum() as i8;
+        }
+
+        let mut result = Vec::with_capacity(clauses.len());
+        let mut conflict = false;
+        for clause in clauses {
+            let mut satisfied = false;
+            let mut reduced = Vec::with_capacity(clause.len());
+            for &lit in clause {
+                let value = self.values[lit.unsigned_abs() as usize];
+                if value == 0 {
+                    reduced.push(lit);
+                } else if value == lit.signum() as i8 {
+                    satisfied = true;
+                    break;
+                }
+            }
+            if !satisfied {
+                if reduced.is_empty() {
+                    conflict = true;
+                    break;
+                }
+                result.push(reduced);
+            }
+        }
+
+        for &lit in lits {
+            self.values[lit.unsigned_abs() as usize] = 0;
+        }
+        if conflict {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Assigns the unit clauses until none is left, and returns the
+    /// remaining clauses with the number of projected variables assigned,
+    /// or `None` if a conflict is found.
+    fn propagate(&mut self, mut clauses: Vec<Vec<i32>>) -> Option<(Vec<Vec<i32>>, usize)> {
+        let mut assigned = 0;
+        let mut units: Vec<i32> = Vec::new();
+        loop {
+            units.clear();
+            for clause in clauses.iter() {
+                if clause.len() == 1 {
+                    units.push(clause[0]);
+                }
+            }
+            if units.is_empty() {
+                return Some((clauses, assigned));
+            }
+
+            units.sort_unstable();
+            units.dedup();
+            if units.windows(2).any(|w| w[0] == -w[1]) {
+                return None;
+            }
+            assigned += units
+                .iter()
+                .filter(|lit| self.projected[lit.unsigned_abs() as usize])
+                .count();
+            clauses = self.assign(&clauses, &units)?;
+        }
+    }
+
+    /// Splits the clauses into groups that do not share variables.
+    fn components(clauses: Vec<Vec<i32>>) -> Vec<Vec<Vec<i32>>> {
+        let mut index: HashMap<u32, usize> = HashMap::new();
+        let mut parent: Vec<usize> = Vec::new();
+        fn find(parent: &mut [usize], mut a: usize) -> usize {
+            while parent[a] != a {
+                parent[a] = parent[parent[a]];
+                a = parent[a];
+            }
+            a
+        }
+
+        for clause in clauses.iter() {
+            let mut root = None;
+            for &lit in clause {
+                let next = parent.len();
+                let var = *index.entry(lit.unsigned_abs()).or_insert(next);
+                if var == next {
+                    parent.push(next);
+                }
+                let var = find(&mut parent, var);
+                match root {
+                    None => root = Some(var),
+                    Some(r) if r != var => parent[var] = r,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut groups: HashMap<usize, usize> = HashMap::new();
+        let mut result: Vec<Vec<Vec<i32>>> = Vec::new();
+        for clause in clauses {
+            let var = index[&clause[0].unsigned_abs()];
+            let root = find(&mut parent, var);
+            let next = result.len();
+            let pos = *groups.entry(root).or_insert(next);
+            if pos == next {
+                result.push(Vec::new());
+            }
+            result[pos].push(clause);
+        }
+        result
+    }
+
+    /// Returns the number of assignments of the given number of projected
+    /// variables, which include the ones occurring in the clauses, that can
+    /// be extended to a model.
+    fn count(&mut self, clauses: Vec<Vec<i32>>, scope: usize) -> BigNum {
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut ret = self.start_count(clauses, scope, &mut stack);
+        loop {
+            let frame = match stack.last_mut() {
+                Some(frame) => frame,
+                None => return ret.unwrap(),
+            };
+
+            match frame {
+                Frame::Product { result, components } => {
+                    if let Some(count) = ret.take() {
+                        if count.is_zero() {
+                            stack.pop();
+                            ret = Some(count);
+                            continue;
+                        }
+                        *result = &*result * &count;
+                    }
+                    match components.pop() {
+                        Some(component) => ret = self.start_component(component, &mut stack),
+                        None => {
+                            ret = Some(std::mem::replace(result, BigNum::zero()));
+                            stack.pop();
+                        }
+                    }
+                }
+                Frame::Branch {
+                    component,
+                    lits,
+                    projected,
+                    scope,
+                    result,
+                    ..
+                } => {
+                    if let Some(count) = ret.take() {
+                        *result = &*result + &count;
+                    }
+                    let satisfiable = !*projected && !result.is_zero();
+                    match lits.pop() {
+                        Some(lit) if !satisfiable => {
+                            let scope = *scope;
+                            ret = match self.assign(component, &[lit]) {
+                                Some(clauses) => self.start_count(clauses, scope, &mut stack),
+                                None => Some(BigNum::zero()),
+                            };
+                        }
+                        _ => {
+                            if let Some(Frame::Branch { key, result, .. }) = stack.pop() {
+                                self.cache.insert(key, result.clone());
+                                ret = Some(result);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Starts counting the clauses as in `count`. Returns the count if it
+    /// is known immediately, otherwise pushes a new frame on the stack.
+    fn start_count(
+        &mut self,
+        clauses: Vec<Vec<i32>>,
+        scope: usize,
+        stack: &mut Vec<Frame>,
+    ) -> Option<BigNum> {
+        let (clauses, assigned) = match self.propagate(clauses) {
+            Some(result) => result,
+            None => return Some(BigNum::zero()),
+        };
+
+        // projected variables not occurring in the clauses are free
+        let free = scope - assigned - self.num_projected(&clauses);
+        let result = BigNum::pow2(free);
+        let components = Counter::components(clauses);
+        if components.is_empty() {
+            Some(result)
+        } else {
+            stack.push(Frame::Product { result, components });
+            None
+        }
+    }
+
+    /// Starts counting the assignments of the projected variables of a
+    /// connected component that can be extended to a model. Returns the
+    /// count if it is cached, otherwise pushes a new frame on the stack.
+    fn start_component(
+        &mut self,
+        mut component: Vec<Vec<i32>>,
+        stack: &mut Vec<Frame>,
+    ) -> Option<BigNum> {
+        component.sort_unstable();
+        let key: Box<[i32]> = component
+            .iter()
+            .flat_map(|clause| clause.iter().copied().chain(std::iter::once(0)))
+            .collect();
+        if let Some(count) = self.cache.get(&key) {
+            return Some(count.clone());
+        }
+
+        // branch on the most frequent projected variable if there is one
+        let mut occurrences: HashMap<u32, usize> = HashMap::new();
+        for clause in component.iter() {
+            for &lit in clause {
+                *occurrences.entry(lit.unsigned_abs()).or_insert(0) += 1;
+            }
+        }
+        let scope = self.num_projected(&component);
+        let (projected, _, Reverse(var)) = occurrences
+            .into_iter()
+            .map(|(var, occ)| (self.projected[var as usize], occ, Reverse(var)))
+            .max()
+            .unwrap();
+        let scope = if projected { scope - 1 } else { scope };
+
+        // the positive branch is taken first
+        stack.push(Frame::Branch {
+            key,
+            component,
+            lits: vec![-(var as i32), var as i32],
+            projected,
+            scope,
+            result: BigNum::zero(),
+        });
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::random::Random;
+    use super::super::{BoolAlg, BoolSat, Solver};
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn random() {
+        let mut random = Random::new(1);
+        for _ in 0..50 {
+            let num_vars = 1 + random.next_below(10) as i32;
+            let mut formula = Formula::new();
+            for _ in 0..num_vars {
+                formula.add_variable();
+            }
+            for _ in 0..random.next_below(3 * num_vars as usize) {
+                let clause: Vec<_> = (0..1 + random.next_below(3))
+                    .map(|_| {
+                        let lit = 1 + random.next_below(num_vars as usize) as i32;
+                        Formula::decode(if random.next_bool() { lit } else { -lit })
+                    })
+                    .collect();
+                if random.next_below(5) == 0 {
+                    formula.add_xor_clause(&clause);
+                } else {
+                    formula.add_clause(&clause);
+                }
+            }
+            let projection: Vec<i32> = (1..=num_vars)
+                .filter(|_| random.next_below(3) != 0)
+                .collect();
+
+            let mut models = HashSet::new();
+            for bits in 0u32..(1 << num_vars) {
+                let value = |lit: i32| ((bits >> (lit.abs() - 1)) & 1 != 0) == (lit > 0);
+                let sat = formula
+                    .clauses()
+                    .iter()
+                    .all(|clause| clause.iter().any(|&lit| value(lit)))
+                    && formula
+                        .xor_clauses()
+                        .iter()
+                        .all(|xor| xor.iter().filter(|&&lit| value(lit)).count() % 2 == 0);
+                if sat {
+                    let proj: Vec<bool> = projection.iter().map(|&lit| value(lit)).collect();
+                    models.insert(proj);
+                }
+            }
+
+            let count = count_models(&formula, &projection);
+            assert_eq!(count, BigNum::from(models.len() as u64));
+        }
+    }
+
+    #[test]
+    fn long_path() {
+        // the path is one component that is split at each level, so the
+        // search is as deep as the path is long
+        let mut formula = Formula::new();
+        let lits: Vec<_> = (0..1000).map(|_| formula.add_variable()).collect();
+        for pair in lits.windows(2) {
+            formula.add_clause(pair);
+        }
+        let projection: Vec<i32> = lits.iter().map(|&lit| Formula::encode(lit)).collect();
+        let count = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || count_models(&formula, &projection))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        // the number of models is a Fibonacci number
+        let (mut a, mut b) = (BigNum::one(), BigNum::one());
+        for _ in 0..lits.len() {
+            let c = &a + &b;
+            a = b;
+            b = c;
+        }
+        assert_eq!(count, b);
+    }
+
+    #[test]
+    fn solver() {
+        let mut alg = Solver::new("record:");
+        let elems: Vec<_> = (0..40).map(|_| alg.bool_add_variable()).collect();
+        let count = alg.bool_count_models(elems.iter().copied());
+        assert_eq!(count, BigNum::pow2(40));
+
+        // at most one of each consecutive pair
+        for i in 0..elems.len() - 1 {
+            let a = alg.bool_not(elems[i]);
+            let b = alg.bool_not(elems[i + 1]);
+            let c = alg.bool_or(a, b);
+            alg.bool_add_clause(&[c]);
+        }
+        let count = alg.bool_count_models(elems.iter().copied());
+        assert_eq!(count, BigNum::from(267914296));
+
+        let count = alg.bool_count_models(elems.iter().take(10).copied());
+        assert_eq!(count, BigNum::from(144));
+        let count = alg.bool_count_models(elems[..10].iter().copied());
+        assert_eq!(
+            count,
+            BigNum::from(alg.bool_find_num_models_method1(elems[..10].iter().copied()) as u64)
+        );
+    }
+}
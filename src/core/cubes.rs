@@ -0,0 +1,310 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Parallel cube-and-conquer enumeration and counting of models.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread::{available_parallelism, spawn, JoinHandle};
+
+use super::{create_solver, Formula, Literal, SatSolver, SolveResult};
+
+/// The configuration of a parallel cube-and-conquer search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CubeConfig {
+    /// The name of the solver created for each thread, see `create_solver`.
+    pub solver: String,
+    /// The number of threads solving the cubes.
+    pub threads: usize,
+    /// The number of positions to split on when they are not given, which
+    /// results in `2^depth` cubes.
+    pub depth: usize,
+}
+
+impl Default for CubeConfig {
+    fn default() -> Self {
+        let threads = available_parallelism().map_or(1, |n| n.get());
+        CubeConfig {
+            solver: String::new(),
+            threads,
+            depth: 64 - (threads as u64).leading_zeros() as usize + 3,
+        }
+    }
+}
+
+/// The shared state of the threads of a search.
+struct Cubes {
+    formula: Formula,
+    projection: Vec<i32>,
+    split: Vec<i32>,
+    next: AtomicUsize,
+    stop: AtomicBool,
+}
+
+impl Cubes {
+    /// Creates the shared state. If no split literals are given, then the
+    /// projected variables occurring in the most clauses are chosen. All
+    /// split literals must be projected, otherwise the same projection
+    /// could be found in several cubes.
+    fn new(formula: Formula, projection: Vec<i32>, mut split: Vec<i32>, depth: usize) -> Self {
+        let projected: HashSet<u32> = projection.iter().map(|lit| lit.unsigned_abs()).collect();
+        if split.is_empty() {
+            let mut occurrences: HashMap<u32, usize> = HashMap::new();
+            for clause in formula.clauses().iter().chain(formula.xor_clauses()) {
+                for lit in clause {
+                    if projected.contains(&lit.unsigned_abs()) {
+                        *occurrences.entry(lit.unsigned_abs()).or_insert(0) += 1;
+                    }
+                }
+            }
+            let mut vars: Vec<(usize, u32)> = occurrences
+                .into_iter()
+                .map(|(var, occ)| (occ, var))
+                .collect();
+            vars.sort_unstable_by(|a, b| b.cmp(a));
+            split.extend(vars.into_iter().take(depth).map(|(_, var)| var as i32));
+        }
+
+        for lit in split.iter() {
+            assert!(
+                projected.contains(&lit.unsigned_abs()),
+                "split literal is not projected"
+            );
+        }
+        assert!(split.len() < 32, "too many split literals");
+
+        Cubes {
+            formula,
+            projection,
+            split,
+            next: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    /// Starts the given number of threads that take the cubes one by one
+    /// and call the given function with the projection of each model. A
+    /// thread stops when the function returns false.
+    fn spawn<F>(self: &Arc<Self>, solver: &str, threads: usize, fun: F) -> Vec<JoinHandle<usize>>
+    where
+        F: Fn(Vec<bool>) -> bool + Clone + Send + 'static,
+    {
+        (0..threads.max(1))
+            .map(|_| {
+                let cubes = self.clone();
+                let solver = solver.to_string();
+                let fun = fun.clone();
+                spawn(move || cubes.conquer(create_solver(&solver), fun))
+            })
+            .collect()
+    }
+
+    /// Solves the cubes in a single thread and returns the number of models
+    /// found. The blocking clauses of earlier cubes are kept, as they are
+    /// satisfied in the later cubes anyway.
+    fn conquer<F>(&self, mut solver: Box<dyn SatSolver>, fun: F) -> usize
+    where
+        F: Fn(Vec<bool>) -> bool,
+    {
+        let literals = self.formula.load_into(solver.as_mut());
+        let decode = |solver: &dyn SatSolver, lit: i32| {
+            let var = literals[(lit.abs() - 1) as usize];
+            if lit < 0 {
+                solver.negate(var)
+            } else {
+                var
+            }
+        };
+        let projection: Vec<Literal> = self
+            .projection
+            .iter()
+            .map(|&lit| decode(solver.as_ref(), lit))
+            .collect();
+
+        let mut count = 0;
+        let mut assumptions: Vec<Literal> = Vec::with_capacity(self.split.len());
+        let mut clause: Vec<Literal> = Vec::with_capacity(projection.len());
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return count;
+            }
+            let cube = self.next.fetch_add(1, Ordering::SeqCst);
+            if cube >= 1 << self.split.len() {
+                return count;
+            }
+
+            assumptions.clear();
+            for (i, &lit) in self.split.iter().enumerate() {
+                let lit = if (cube >> i) & 1 != 0 { lit } else { -lit };
+                assumptions.push(decode(solver.as_ref(), lit));
+            }
+
+            while !self.stop.load(Ordering::Relaxed)
+                && solver.solve_with(&assumptions) == SolveResult::Sat
+            {
+                let values: Vec<bool> = projection
+                    .iter()
+                    .map(|&lit| solver.get_value(lit))
+                    .collect();
+
+                clause.clear();
+                for (&lit, &val) in projection.iter().zip(values.iter()) {
+                    clause.push(if val { solver.negate(lit) } else { lit });
+                }
+                solver.add_clause(&clause);
+
+                count += 1;
+                if !fun(values) {
+                    self.stop.store(true, Ordering::Relaxed);
+                    return count;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the number of models of the formula projected onto the given
+/// literals, solving the cubes over the split literals in parallel.
+pub(super) fn count_models(
+    formula: Formula,
+    projection: Vec<i32>,
+    split: Vec<i32>,
+    config: &CubeConfig,
+) -> usize {
+    let cubes = Arc::new(Cubes::new(formula, projection, split, config.depth));
+    cubes
+        .spawn(&config.solver, config.threads, |_| true)
+        .into_iter()
+        .map(|thread| thread.join().expect("cube solver failed"))
+        .sum()
+}
+
+/// Returns an iterator over the models of the formula projected onto the
+/// given literals, solving the cubes over the split literals in parallel.
+pub(super) fn models(
+    formula: Formula,
+    projection: Vec<i32>,
+    split: Vec<i32>,
+    config: &CubeConfig,
+) -> ParModels {
+    let cubes = Arc::new(Cubes::new(formula, projection, split, config.depth));
+    let (sender, receiver) = sync_channel(64);
+    let threads = cubes.spawn(&config.solver, config.threads, move |values| {
+        sender.send(values).is_ok()
+    });
+    ParModels {
+        cubes,
+        receiver,
+        threads,
+    }
+}
+
+/// An iterator over the models of a problem found by parallel threads,
+/// each solving its own cubes with its own solver. The models arrive in no
+/// particular order. Dropping the iterator stops the threads after their
+/// next model. A panic of a thread is propagated when the models run out.
+pub struct ParModels {
+    cubes: Arc<Cubes>,
+    receiver: Receiver<Vec<bool>>,
+    threads: Vec<JoinHandle<usize>>,
+}
+
+impl Iterator for ParModels {
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver.recv() {
+            Ok(values) => Some(values),
+            Err(_) => {
+                // all threads have stopped, either normally or by a panic
+                for thread in self.threads.drain(..) {
+                    thread.join().expect("cube solver failed");
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Drop for ParModels {
+    fn drop(&mut self) {
+        self.cubes.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Shape, Solver, TensorAlg, TensorSat};
+    use super::*;
+
+    #[test]
+    fn cubes() {
+        let mut alg = Solver::new("record:");
+        let t1 = alg.tensor_add_variable(Shape::new(vec![3]));
+        let t2 = alg.tensor_add_variable(Shape::new(vec![5]));
+        let t3 = alg.tensor_any(t1.clone());
+        alg.tensor_add_clause1(t3);
+        let t3 = alg.tensor_exactly(t2.clone(), 2, Default::default());
+        alg.tensor_add_clause1(t3);
+
+        let config = CubeConfig {
+            threads: 3,
+            depth: 2,
+            ..Default::default()
+        };
+        let elems = [t1.clone(), t2.clone()];
+        assert_eq!(alg.tensor_par_find_num_models(&elems, &[], &config), 70);
+        let split = std::slice::from_ref(&t1);
+        assert_eq!(alg.tensor_par_find_num_models(&elems, split, &config), 70);
+
+        // unconstrained positions multiply the number of models
+        let t4 = alg.tensor_add_variable(Shape::new(vec![3]));
+        let mut models: Vec<Vec<bool>> = alg
+            .tensor_par_models(&[t1.clone(), t2, t4], split, &config)
+            .map(|values| {
+                assert_eq!(values.len(), 3);
+                let a = (0..3).map(|i| values[0].very_slow_get(&[i]));
+                let b = (0..5).map(|i| values[1].very_slow_get(&[i]));
+                a.chain(b).collect()
+            })
+            .collect();
+        assert_eq!(models.len(), 70 * 8);
+        models.sort();
+        models.dedup();
+        assert_eq!(models.len(), 70);
+
+        // the threads stop when the iterator is dropped
+        let models = alg.tensor_par_models(&elems, &[], &config);
+        assert_eq!(models.take(5).count(), 5);
+        assert_eq!(alg.tensor_find_num_models(&elems), 70);
+    }
+
+    #[test]
+    #[should_panic(expected = "cube solver failed")]
+    fn failed() {
+        let mut alg = Solver::new("record:");
+        let t1 = alg.tensor_add_variable(Shape::new(vec![3]));
+        let config = CubeConfig {
+            solver: "bogus".into(),
+            threads: 2,
+            depth: 1,
+        };
+        alg.tensor_par_models(&[t1], &[], &config).count();
+    }
+}
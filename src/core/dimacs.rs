@@ -0,0 +1,563 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Storing CNF formulas and reading and writing them in the DIMACS format.
+
+use std::{error, fmt, io};
+
+use super::{Literal, SatSolver};
+
+/// Error type for reporting problems with reading DIMACS files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DimacsError {
+    /// The line number (starting from 1) where the error was found.
+    pub line: usize,
+    /// The description of the problem.
+    pub msg: String,
+}
+
+impl DimacsError {
+    pub(super) fn new(line: usize, msg: String) -> Self {
+        DimacsError { line, msg }
+    }
+}
+
+impl fmt::Display for DimacsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl error::Error for DimacsError {}
+
+/// Options controlling how a formula is written in the DIMACS format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DimacsOptions {
+    /// Write XOR clauses as `x` lines understood by CryptoMiniSat instead
+    /// of expanding them into regular clauses.
+    pub xor_lines: bool,
+    /// Write the assumptions as unit clauses instead of a comment line.
+    pub assumption_units: bool,
+}
+
+/// A CNF formula together with XOR clauses. The literals follow the DIMACS
+/// convention: variables are numbered from 1 and the negation of a literal
+/// is its negative value, which is stored in the `value` of the literal as
+/// a two's complement number.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Formula {
+    num_variables: u32,
+    clauses: Vec<Vec<i32>>,
+    xor_clauses: Vec<Vec<i32>>,
+}
+
+impl Formula {
+    /// Creates a new empty formula.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the DIMACS number of the given literal.
+    pub fn encode(lit: Literal) -> i32 {
+        lit.value as i32
+    }
+
+    /// Returns the literal with the given DIMACS number.
+    pub fn decode(lit: i32) -> Literal {
+        debug_assert!(lit != 0 && lit != i32::MIN);
+        Literal { value: lit as u32 }
+    }
+
+    /// Adds a fresh variable to the formula.
+    pub fn add_variable(&mut self) -> Literal {
+        self.num_variables += 1;
+        Formula::decode(self.num_variables as i32)
+    }
+
+    /// Negates the given literal.
+    pub fn negate(lit: Literal) -> Literal {
+        Formula::decode(-Formula::encode(lit))
+    }
+
+    /// Adds the given clause to the formula.
+    pub fn add_clause(&mut self, lits: &[Literal]) {
+        self.clauses
+            .push(lits.iter().map(|lit| Formula::encode(*lit)).collect());
+    }
+
+    /// Adds an XOR clause to the formula where the binary sum of the
+    /// literals must be zero.
+    pub fn add_xor_clause(&mut self, lits: &[Literal]) {
+        self.xor_clauses
+            .push(lits.iter().map(|lit| Formula::encode(*lit)).collect());
+    }
+
+    /// Returns the number of variables in the formula.
+    pub fn num_variables(&self) -> u32 {
+        self.num_variables
+    }
+
+    /// Returns the regular clauses of the formula.
+    pub fn clauses(&self) -> &[Vec<i32>] {
+        &self.clauses
+    }
+
+    /// Returns the XOR clauses of the formula.
+    pub fn xor_clauses(&self) -> &[Vec<i32>] {
+        &self.xor_clauses
+    }
+
+    /// Calls the given function with XOR clauses of at most three literals
+    /// that are equivalent to the given one. Longer XOR clauses are split
+    /// into a chain using fresh variables after the given number of
+    /// variables, which is increased accordingly.
+    pub(super) fn split_xor_clause<F>(xor: &[i32], num_variables: &mut u32, mut fun: F)
+    where
+        F: FnMut(&[i32]),
+    {
+        if xor.len() <= 3 {
+            fun(xor);
+            return;
+        }
+
+        // each fresh variable is the binary sum of the earlier literals
+        let mut last = xor[0];
+        for &lit in xor[1..xor.len() - 2].iter() {
+            *num_variables += 1;
+            let var = *num_variables as i32;
+            fun(&[last, lit, var]);
+            last = var;
+        }
+        fun(&[last, xor[xor.len() - 2], xor[xor.len() - 1]]);
+    }
+
+    /// Calls the given function with the regular clauses equivalent to the
+    /// XOR clause, these forbid all assignments with odd binary sum. Long
+    /// XOR clauses are split first, see `split_xor_clause`.
+    pub(super) fn expand_xor_clause<F>(xor: &[i32], num_variables: &mut u32, mut fun: F)
+    where
+        F: FnMut(&[i32]),
+    {
+        let mut clause = Vec::with_capacity(3);
+        Formula::split_xor_clause(xor, num_variables, |xor| {
+            for mask in 0u32..(1 << xor.len()) {
+                if mask.count_ones() % 2 == 1 {
+                    clause.clear();
+                    clause.extend(xor.iter().enumerate().map(|(i, lit)| {
+                        if (mask >> i) & 1 != 0 {
+                            -*lit
+                        } else {
+                            *lit
+                        }
+                    }));
+                    fun(&clause);
+                }
+            }
+        });
+    }
+
+    /// Writes the formula in DIMACS format into the given output. The
+    /// assumptions are written either as a comment or as unit clauses.
+    pub fn write_dimacs(
+        &self,
+        out: &mut dyn io::Write,
+        assumptions: &[Literal],
+        options: DimacsOptions,
+    ) -> io::Result<()> {
+        let mut num_variables = self.num_variables;
+        let mut num_clauses = self.clauses.len();
+        if options.xor_lines {
+            num_clauses += self
+                .xor_clauses
+                .iter()
+                .filter(|xor| !xor.is_empty())
+                .count();
+        } else {
+            for xor in self.xor_clauses.iter() {
+                Formula::expand_xor_clause(xor, &mut num_variables, |_| num_clauses += 1);
+            }
+        }
+        if options.assumption_units {
+            num_clauses += assumptions.len();
+        }
+
+        writeln!(out, "p cnf {} {}", num_variables, num_clauses)?;
+        if !options.assumption_units && !assumptions.is_empty() {
+            write!(out, "c assumptions")?;
+            for lit in assumptions {
+                write!(out, " {}", Formula::encode(*lit))?;
+            }
+            writeln!(out, " 0")?;
+        }
+
+        fn write_line(out: &mut dyn io::Write, prefix: &str, lits: &[i32]) -> io::Result<()> {
+            write!(out, "{}", prefix)?;
+            for lit in lits {
+                write!(out, "{} ", lit)?;
+            }
+            writeln!(out, "0")
+        }
+
+        for clause in self.clauses.iter() {
+            write_line(out, "", clause)?;
+        }
+
+        let mut num_variables = self.num_variables;
+        for xor in self.xor_clauses.iter() {
+            if options.xor_lines {
+                // the empty x line would be false, but the empty XOR clause is true
+                if xor.is_empty() {
+                    continue;
+                }
+                // the x lines require the binary sum to be one
+                let mut xor = xor.clone();
+                if let Some(lit) = xor.first_mut() {
+                    *lit = -*lit;
+                }
+                write_line(out, "x", &xor)?;
+            } else {
+                let mut result = Ok(());
+                Formula::expand_xor_clause(xor, &mut num_variables, |clause| {
+                    if result.is_ok() {
+                        result = write_line(out, "", clause);
+                    }
+                });
+                result?;
+            }
+        }
+
+        if options.assumption_units {
+            for lit in assumptions {
+                write_line(out, "", &[Formula::encode(*lit)])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a formula in DIMACS format from the given input. Besides the
+    /// regular clauses, XOR clauses given on `x` lines are also accepted.
+    pub fn read_dimacs(input: &mut dyn io::BufRead) -> Result<Formula, DimacsError> {
+        let mut formula = Formula::new();
+        let mut header: Option<(u32, usize)> = None;
+        let mut clause: Vec<i32> = Vec::new();
+        let mut is_xor = false;
+        let mut line_num = 0;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            line_num += 1;
+            match input.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => return Err(DimacsError::new(line_num, err.to_string())),
+            }
+
+            let mut rest = line.trim();
+            if rest.is_empty() || rest.starts_with('c') {
+                continue;
+            } else if rest.starts_with('%') {
+                break;
+            } else if rest.starts_with('p') {
+                if header.is_some() {
+                    return Err(DimacsError::new(line_num, "duplicate header".into()));
+                }
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let vars = parts.get(2).and_then(|s| s.parse().ok());
+                let clauses = parts.get(3).and_then(|s| s.parse().ok());
+                match (parts.len(), parts.get(1), vars, clauses) {
+                    (4, Some(&"cnf"), Some(vars), Some(clauses)) if vars < i32::MAX as u32 => {
+                        header = Some((vars, clauses));
+                        formula.num_variables = vars;
+                    }
+                    _ => return Err(DimacsError::new(line_num, "invalid header".into())),
+                }
+                continue;
+            }
+
+            let num_variables = match header {
+                Some((vars, _)) => vars as i32,
+                None => return Err(DimacsError::new(line_num, "missing header".into())),
+            };
+
+            if clause.is_empty() && !is_xor && rest.starts_with('x') {
+                is_xor = true;
+                rest = &rest[1..];
+            }
+
+            for token in rest.split_whitespace() {
+                let lit: i32 = match token.parse() {
+                    Ok(lit) if lit != i32::MIN => lit,
+                    _ => {
+                        let msg = format!("invalid literal {}", token);
+                        return Err(DimacsError::new(line_num, msg));
+                    }
+                };
+                if lit == 0 {
+                    if is_xor {
+                        // the x lines require the binary sum to be one
+                        if let Some(lit) = clause.first_mut() {
+                            *lit = -*lit;
+                            formula.xor_clauses.push(clause.clone());
+                        } else {
+                            formula.clauses.push(Vec::new());
+                        }
+                    } else {
+                        formula.clauses.push(clause.clone());
+                    }
+                    clause.clear();
+                    is_xor = false;
+                } else if lit.abs() > num_variables {
+                    let msg = format!("literal {} is out of range", lit);
+                    return Err(DimacsError::new(line_num, msg));
+                } else {
+                    clause.push(lit);
+                }
+            }
+        }
+
+        if !clause.is_empty() || is_xor {
+            return Err(DimacsError::new(line_num, "unterminated clause".into()));
+        }
+        match header {
+            None => Err(DimacsError::new(line_num, "missing header".into())),
+            Some((_, num_clauses)) if num_clauses != formula.num_clauses() => {
+                let msg = format!(
+                    "expected {} clauses but found {}",
+                    num_clauses,
+                    formula.num_clauses()
+                );
+                Err(DimacsError::new(line_num, msg))
+            }
+            _ => Ok(formula),
+        }
+    }
+
+    /// Returns the total number of regular and XOR clauses.
+    pub fn num_clauses(&self) -> usize {
+        self.clauses.len() + self.xor_clauses.len()
+    }
+
+    /// Adds the variables and clauses of this formula to the given solver
+    /// and returns the literals of the solver for the variables. The XOR
+    /// clauses are split into chains of short ones, whose fresh variables
+    /// are not returned.
+    pub fn load_into(&self, solver: &mut dyn SatSolver) -> Vec<Literal> {
+        let mut literals: Vec<Literal> = (0..self.num_variables)
+            .map(|_| solver.add_variable())
+            .collect();
+        fn decode(solver: &dyn SatSolver, literals: &[Literal], lit: i32) -> Literal {
+            let var = literals[(lit.abs() - 1) as usize];
+            if lit < 0 {
+                solver.negate(var)
+            } else {
+                var
+            }
+        }
+
+        let mut temp: Vec<Literal> = Vec::new();
+        for clause in self.clauses.iter() {
+            temp.clear();
+            temp.extend(clause.iter().map(|lit| decode(solver, &literals, *lit)));
+            solver.add_clause(&temp);
+        }
+
+        let mut num_variables = self.num_variables;
+        let mut pieces: Vec<Vec<i32>> = Vec::new();
+        for xor in self.xor_clauses.iter() {
+            pieces.clear();
+            Formula::split_xor_clause(xor, &mut num_variables, |xor| pieces.push(xor.to_vec()));
+            while literals.len() < num_variables as usize {
+                literals.push(solver.add_variable());
+            }
+
+            for xor in pieces.iter() {
+                if xor.len() == 3 {
+                    let lit1 = decode(solver, &literals, xor[0]);
+                    let lit2 = decode(solver, &literals, xor[1]);
+                    let lit3 = decode(solver, &literals, xor[2]);
+                    solver.add_xor_clause(lit1, lit2, lit3);
+                } else {
+                    Formula::expand_xor_clause(xor, &mut num_variables, |clause| {
+                        temp.clear();
+                        temp.extend(clause.iter().map(|lit| decode(solver, &literals, *lit)));
+                        solver.add_clause(&temp);
+                    });
+                }
+            }
+        }
+
+        literals.truncate(self.num_variables as usize);
+        literals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SolveResult;
+    use super::*;
+
+    #[test]
+    fn write() {
+        let mut formula = Formula::new();
+        let a = formula.add_variable();
+        let b = formula.add_variable();
+        let c = formula.add_variable();
+        formula.add_clause(&[a, Formula::negate(b)]);
+        formula.add_xor_clause(&[a, b, c]);
+
+        let mut out: Vec<u8> = Vec::new();
+        let options = Default::default();
+        formula.write_dimacs(&mut out, &[c], options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "p cnf 3 5\nc assumptions 3 0\n1 -2 0\n-1 2 3 0\n1 -2 3 0\n\
+             1 2 -3 0\n-1 -2 -3 0\n"
+        );
+
+        let mut out: Vec<u8> = Vec::new();
+        let options = DimacsOptions {
+            xor_lines: true,
+            assumption_units: true,
+        };
+        formula.write_dimacs(&mut out, &[c], options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "p cnf 3 3\n1 -2 0\nx-1 2 3 0\n3 0\n"
+        );
+
+        // the empty XOR clause is true and it is not written
+        formula.add_xor_clause(&[]);
+        let mut out: Vec<u8> = Vec::new();
+        formula.write_dimacs(&mut out, &[], options).unwrap();
+        assert_eq!(
+            String::from_utf8(out.clone()).unwrap(),
+            "p cnf 3 2\n1 -2 0\nx-1 2 3 0\n"
+        );
+        let formula2 = Formula::read_dimacs(&mut out.as_slice()).unwrap();
+        assert_eq!(formula2.clauses(), formula.clauses());
+        assert_eq!(formula2.xor_clauses(), &formula.xor_clauses()[..1]);
+    }
+
+    #[test]
+    fn solver() {
+        use super::super::{BoolAlg, BoolSat, Solver};
+
+        let mut alg = Solver::new("record:");
+        let a = alg.bool_add_variable();
+        let b = alg.bool_add_variable();
+        let c = alg.bool_or(a, b);
+        alg.bool_add_clause(&[c]);
+
+        let mut out: Vec<u8> = Vec::new();
+        let formula = alg.get_formula().unwrap();
+        formula
+            .write_dimacs(&mut out, &[a], Default::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "p cnf 4 5\nc assumptions 2 0\n1 0\n-2 4 0\n-3 4 0\n2 3 -4 0\n4 0\n"
+        );
+    }
+
+    #[test]
+    fn read() {
+        let text = "c example\np cnf 3 3\n1 -2\n 0 -1 2 0\nx1 2 3 0\n%\n0\n";
+        let formula = Formula::read_dimacs(&mut text.as_bytes()).unwrap();
+        assert_eq!(formula.num_variables(), 3);
+        assert_eq!(formula.clauses(), &[vec![1, -2], vec![-1, 2]]);
+        assert_eq!(formula.xor_clauses(), &[vec![-1, 2, 3]]);
+
+        let mut out: Vec<u8> = Vec::new();
+        let options = DimacsOptions {
+            xor_lines: true,
+            assumption_units: false,
+        };
+        formula.write_dimacs(&mut out, &[], options).unwrap();
+        let formula2 = Formula::read_dimacs(&mut out.as_slice()).unwrap();
+        assert_eq!(formula, formula2);
+
+        let names = [
+            "",
+            "record:",
+            #[cfg(feature = "batsat")]
+            "batsat",
+            #[cfg(feature = "cadical")]
+            "cadical",
+        ];
+        for name in names {
+            let mut sat = super::super::create_solver(name);
+            let lits = formula.load_into(sat.as_mut());
+            assert_eq!(lits.len(), 3);
+            assert_eq!(sat.solve_with(&[lits[0]]), SolveResult::Sat);
+            assert!(sat.get_value(lits[1]));
+            assert!(sat.get_value(lits[2]));
+            let result = sat.solve_with(&[lits[0], sat.negate(lits[2])]);
+            assert_eq!(result, SolveResult::Unsat);
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        let check = |text: &str, line: usize| {
+            let err = Formula::read_dimacs(&mut text.as_bytes()).unwrap_err();
+            assert_eq!(err.line, line, "{}", err);
+        };
+        check("1 2 0\n", 1);
+        check("c\np cnf 2\n", 2);
+        check("p cnf 2 1\n1 2 0\np cnf 2 1\n", 3);
+        check("p cnf 2 1\n\n1 a 0\n", 3);
+        check("p cnf 2 1\n1 3 0\n", 2);
+        check("p cnf 2 1\n1 2\n", 3);
+        check("p cnf 2 2\n1 2 0\n", 3);
+        check("p cnf 2 1\n-2147483648 1 0\n", 2);
+    }
+
+    #[test]
+    fn long_xor() {
+        let text = "p cnf 40 2\nx 0\nx1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 \
+            21 22 23 24 25 26 27 28 29 30 31 32 33 34 35 36 37 38 39 40 0\n";
+        let formula = Formula::read_dimacs(&mut text.as_bytes()).unwrap();
+        assert_eq!(formula.clauses(), &[Vec::<i32>::new()]);
+        assert_eq!(formula.xor_clauses().len(), 1);
+
+        let mut out: Vec<u8> = Vec::new();
+        formula
+            .write_dimacs(&mut out, &[], Default::default())
+            .unwrap();
+        let formula2 = Formula::read_dimacs(&mut out.as_slice()).unwrap();
+        assert_eq!(formula2.num_variables(), 40 + 37);
+        assert_eq!(formula2.clauses().len(), 1 + 38 * 4);
+
+        // the first variable is the binary sum of the others
+        let mut formula = Formula::new();
+        let lits: Vec<Literal> = (0..40).map(|_| formula.add_variable()).collect();
+        let mut xor = lits.clone();
+        xor[0] = Formula::negate(xor[0]);
+        formula.add_xor_clause(&xor);
+        let mut sat = super::super::create_solver("");
+        let lits2 = formula.load_into(sat.as_mut());
+        assert_eq!(lits2.len(), 40);
+        let mut assumptions: Vec<Literal> = lits2[1..].to_vec();
+        assert_eq!(sat.solve_with(&assumptions), SolveResult::Sat);
+        assert!(!sat.get_value(lits2[0]));
+        assumptions[0] = sat.negate(assumptions[0]);
+        assert_eq!(sat.solve_with(&assumptions), SolveResult::Sat);
+        assert!(sat.get_value(lits2[0]));
+    }
+}
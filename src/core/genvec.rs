@@ -20,7 +20,7 @@
 use bit_vec::{BitBlock as _, BitVec};
 use std::iter::{Extend, FromIterator, FusedIterator};
 
-use super::Literal;
+use super::{AigEdge, BddNode, Literal, SymExpr};
 
 /// A unifying interface for regular and bit vectors.
 pub trait GenVector<ELEM>
@@ -558,10 +558,30 @@ impl GenElem for usize {
     type GenVector = Wrapper<Vec<Self>>;
 }
 
+impl GenElem for i64 {
+    type GenVector = Wrapper<Vec<Self>>;
+}
+
+impl GenElem for u64 {
+    type GenVector = Wrapper<Vec<Self>>;
+}
+
 impl GenElem for Literal {
     type GenVector = Wrapper<Vec<Self>>;
 }
 
+impl GenElem for AigEdge {
+    type GenVector = Wrapper<Vec<Self>>;
+}
+
+impl GenElem for BddNode {
+    type GenVector = Wrapper<Vec<Self>>;
+}
+
+impl GenElem for SymExpr {
+    type GenVector = Wrapper<Vec<Self>>;
+}
+
 impl GenElem for () {
     type GenVector = UnitVec;
 }
@@ -21,13 +21,61 @@ mod genvec;
 pub use genvec::{GenElem, GenVec, GenVector};
 
 mod solver;
-pub use solver::{create_solver, Literal, SatSolver};
+pub use solver::{
+    create_solver, External, Interrupt, Interrupted, Limits, Literal, Portfolio, Recorder,
+    SatSolver, SolveResult, Statistics,
+};
+
+mod dimacs;
+pub use dimacs::{DimacsError, DimacsOptions, Formula};
 
 mod tensor;
 pub use tensor::{Shape, Tensor, TensorAlg, TensorSat};
 
 mod boolean;
-pub use boolean::{BoolAlg, BoolSat, Boolean, Solver};
+pub use boolean::{BitParallel, BoolAlg, BoolSat, Boolean, Solver};
+
+mod aig;
+pub use aig::{Aig, AigEdge};
+
+mod bdd;
+pub use bdd::{Bdd, BddModels, BddNode};
+
+mod symbolic;
+pub use symbolic::{SymExpr, Symbolic};
+
+mod cardinality;
+pub use cardinality::CardEncoding;
+
+mod pseudo;
+pub use pseudo::PbEncoding;
+
+mod optimize;
+pub use optimize::{LexOptimum, Optimum, Strategy};
+
+mod models;
+pub use models::Models;
+
+mod bignum;
+pub use bignum::BigNum;
+
+mod counting;
+
+mod random;
+
+mod approx;
+pub use approx::ApproxCount;
+
+mod sampler;
+
+mod cubes;
+pub use cubes::{CubeConfig, ParModels};
+
+mod proof;
+pub use proof::{Proof, ProofError, ProofResult, ProofStep};
+
+mod named;
+pub use named::NamedConstraints;
 
 mod progress;
 pub use progress::{add_progress, del_progress, set_progress};
@@ -0,0 +1,151 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Lazy enumeration of the models of a problem.
+
+use super::{Limits, Tensor, TensorSat};
+
+/// An iterator over the models of a problem projected onto a list of
+/// tensors. Each returned projection is excluded with a blocking clause
+/// over the projected positions only, so every projection is returned
+/// once. The blocking clauses stay in the solver, thus a new iterator over
+/// the same tensors continues where the previous one stopped. Use `take`
+/// to limit the number of models.
+///
+/// The enumeration relies on blocking clauses alone. Neither the BatSat
+/// nor the CaDiCaL binding allows to set the phase of a variable or to
+/// restrict the decisions to the projected variables after the variable
+/// is created, so these tricks are not available. Both solvers save the
+/// phases of the variables, so each search starts from the previous model
+/// and only has to flip a few projected positions.
+pub struct Models<'a, ALG>
+where
+    ALG: TensorSat,
+{
+    alg: &'a mut ALG,
+    assumptions: Vec<ALG::Elem>,
+    elems: Vec<ALG::Elem>,
+    limits: Limits,
+    count: usize,
+    interrupted: bool,
+    exhausted: bool,
+}
+
+impl<'a, ALG> Models<'a, ALG>
+where
+    ALG: TensorSat,
+{
+    /// Creates a new iterator over the models satisfying the assumptions.
+    pub fn new(alg: &'a mut ALG, assumptions: &[ALG::Elem], elems: &[ALG::Elem]) -> Self {
+        Models {
+            alg,
+            assumptions: assumptions.to_vec(),
+            elems: elems.to_vec(),
+            limits: Default::default(),
+            count: 0,
+            interrupted: false,
+            exhausted: false,
+        }
+    }
+
+    /// Sets the resource limits for each call of the solver. When the
+    /// resources run out, the iteration stops and `is_interrupted` returns
+    /// true, but calling `next` again continues the search.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the number of models returned by this iterator so far.
+    pub fn num_models(&self) -> usize {
+        self.count
+    }
+
+    /// Returns true if the last call of the solver ran out of resources.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// Returns true if all models have been returned.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+}
+
+impl<'a, ALG> Iterator for Models<'a, ALG>
+where
+    ALG: TensorSat,
+{
+    type Item = Vec<Tensor<bool>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+
+        let result =
+            self.alg
+                .tensor_find_one_model_limited(&self.assumptions, &self.elems, &self.limits);
+        self.interrupted = result.is_err();
+        match result {
+            Ok(Some(values)) => {
+                self.alg.tensor_add_blocking_clause(&self.elems, &values);
+                self.count += 1;
+                Some(values)
+            }
+            Ok(None) => {
+                self.exhausted = true;
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Shape, Solver, TensorAlg};
+    use super::*;
+
+    #[test]
+    fn models() {
+        let mut alg = Solver::new("");
+        let t1 = alg.tensor_add_variable(Shape::new(vec![3]));
+        let t3 = alg.tensor_exactly(t1.clone(), 2, Default::default());
+        alg.tensor_add_clause1(t3);
+
+        let elems = std::slice::from_ref(&t1);
+        let mut models = alg.tensor_models(&[], elems);
+        assert_eq!(models.by_ref().take(2).count(), 2);
+        assert_eq!(models.num_models(), 2);
+        assert!(!models.is_exhausted());
+
+        let models = alg.tensor_models(&[], elems);
+        let rest: Vec<Vec<Tensor<bool>>> = models.collect();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].len(), 1);
+
+        // all projections are excluded, so the problem became unsatisfiable
+        let mut alg = Solver::new("");
+        let t2 = alg.tensor_add_variable(Shape::new(vec![2]));
+        let t3 = alg.tensor_any(t2.clone());
+        let mut models = alg.tensor_models(&[t3], &[t2]);
+        assert_eq!(models.by_ref().count(), 3);
+        assert!(models.is_exhausted());
+        assert!(!models.is_interrupted());
+    }
+}
@@ -0,0 +1,194 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Labelled constraints that can explain why a problem has no solution.
+
+use super::{Interrupted, Limits, Shape, Tensor, TensorSat};
+
+/// A collection of labelled constraints over a tensor solver. Each label
+/// gets its own activation variable, which is assumed true when solving,
+/// so the labels responsible for unsatisfiability can be identified.
+pub struct NamedConstraints<ALG>
+where
+    ALG: TensorSat,
+{
+    names: Vec<String>,
+    activations: Vec<ALG::Elem>,
+}
+
+impl<ALG> Default for NamedConstraints<ALG>
+where
+    ALG: TensorSat,
+{
+    fn default() -> Self {
+        NamedConstraints {
+            names: Vec::new(),
+            activations: Vec::new(),
+        }
+    }
+}
+
+impl<ALG> NamedConstraints<ALG>
+where
+    ALG: TensorSat,
+{
+    /// Creates an empty collection of constraints.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the labels registered so far.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns the activation variable of the given label, creating a new
+    /// one if the label is not registered yet.
+    fn activation(&mut self, alg: &mut ALG, name: &str) -> ALG::Elem {
+        if let Some(pos) = self.names.iter().position(|n| n == name) {
+            self.activations[pos].clone()
+        } else {
+            let act = alg.tensor_add_variable(Shape::new(vec![]));
+            self.names.push(name.into());
+            self.activations.push(act.clone());
+            act
+        }
+    }
+
+    /// Adds the given (disjunctive) clause to the solver under the given
+    /// label. Several clauses can share the same label.
+    pub fn add_clause(&mut self, alg: &mut ALG, name: &str, clause: &[ALG::Elem]) {
+        let act = self.activation(alg, name);
+        let shape = match clause.first() {
+            Some(elem) => alg.shape(elem).clone(),
+            None => Shape::new(vec![]),
+        };
+        let act = alg.tensor_polymer(act, shape, &[]);
+        let mut clause2 = Vec::with_capacity(clause.len() + 1);
+        clause2.push(alg.tensor_not(act));
+        clause2.extend(clause.iter().cloned());
+        alg.tensor_add_clause(&clause2);
+    }
+
+    /// Adds the given 1-element clause to the solver under the given label.
+    pub fn add_clause1(&mut self, alg: &mut ALG, name: &str, elem1: ALG::Elem) {
+        self.add_clause(alg, name, &[elem1]);
+    }
+
+    /// Runs the solver with all labelled constraints enabled and returns a
+    /// model if it exists.
+    pub fn find_one_model(&self, alg: &mut ALG, elems: &[ALG::Elem]) -> Option<Vec<Tensor<bool>>> {
+        alg.tensor_find_one_model(&self.activations, elems)
+    }
+
+    /// Returns `None` if the labelled constraints are satisfiable, otherwise
+    /// returns a minimal set of labels whose constraints cannot be satisfied
+    /// together. The set is found by deleting labels one by one.
+    pub fn find_conflict(
+        &self,
+        alg: &mut ALG,
+        limits: &Limits,
+    ) -> Result<Option<Vec<String>>, Interrupted> {
+        let all: Vec<usize> = (0..self.activations.len()).collect();
+        let mut core = match self.find_core(alg, &all, limits)? {
+            Some(core) => core,
+            None => return Ok(None),
+        };
+
+        // every label before pos is necessary for the conflict
+        let mut pos = 0;
+        while pos < core.len() {
+            let mut subset = core.clone();
+            subset.remove(pos);
+            match self.find_core(alg, &subset, limits)? {
+                Some(smaller) => core = smaller,
+                None => pos += 1,
+            }
+        }
+
+        Ok(Some(
+            core.into_iter().map(|i| self.names[i].clone()).collect(),
+        ))
+    }
+
+    /// Solves the problem with the given labels enabled, and returns the
+    /// labels responsible for the conflict or `None` if there is a model.
+    fn find_core(
+        &self,
+        alg: &mut ALG,
+        subset: &[usize],
+        limits: &Limits,
+    ) -> Result<Option<Vec<usize>>, Interrupted> {
+        let assumptions: Vec<ALG::Elem> = subset
+            .iter()
+            .map(|&i| self.activations[i].clone())
+            .collect();
+        if alg
+            .tensor_find_one_model_limited(&assumptions, &[], limits)?
+            .is_some()
+        {
+            return Ok(None);
+        }
+
+        let masks = alg.tensor_failed_assumptions(&assumptions);
+        Ok(Some(
+            subset
+                .iter()
+                .zip(masks.iter())
+                .filter(|(_, mask)| mask.scalar())
+                .map(|(&i, _)| i)
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Solver, TensorAlg};
+    use super::*;
+
+    #[test]
+    fn conflict() {
+        let mut alg = Solver::new("");
+        let mut named = NamedConstraints::new();
+        let x = alg.tensor_add_variable(Shape::new(vec![3]));
+        let y = alg.tensor_add_variable(Shape::new(vec![3]));
+
+        named.add_clause1(&mut alg, "x", x.clone());
+        named.add_clause(&mut alg, "x or y", &[x.clone(), y.clone()]);
+        let limits = Default::default();
+        assert_eq!(named.find_conflict(&mut alg, &limits), Ok(None));
+        assert!(named.find_one_model(&mut alg, &[]).is_some());
+
+        let not_y = alg.tensor_not(y.clone());
+        named.add_clause1(&mut alg, "not y", not_y);
+        let any_y = alg.tensor_any(y);
+        named.add_clause1(&mut alg, "any y", any_y);
+        let not_x = alg.tensor_not(x);
+        named.add_clause1(&mut alg, "not x", not_x);
+        assert_eq!(named.names().len(), 5);
+        assert!(named.find_one_model(&mut alg, &[]).is_none());
+
+        let mut conflict = named.find_conflict(&mut alg, &limits).unwrap().unwrap();
+        conflict.sort();
+        assert!(
+            conflict == ["any y", "not y"]
+                || conflict == ["not x", "x"]
+                || conflict == ["not x", "not y", "x or y"]
+        );
+    }
+}
@@ -0,0 +1,617 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Minimization of pseudo-boolean objectives with incremental SAT calls.
+
+use std::time::Instant;
+
+use super::{
+    BoolSat, CardEncoding, GenElem, GenVec, GenVector as _, Interrupted, Limits, PbEncoding, Tensor,
+};
+
+/// The search strategy used to find an optimal model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Finds better and better models until no better one exists.
+    #[default]
+    Linear,
+    /// Halves the interval between the lower and upper bounds in each step.
+    Binary,
+    /// Raises the lower bound using unsatisfiable cores until a model is
+    /// found that attains it.
+    CoreGuided,
+}
+
+/// The best model found by an optimization together with its cost.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Optimum {
+    /// The value of the objective for the returned model.
+    pub cost: i64,
+    /// The proven bound for the objective. The solver has shown that no
+    /// model has a better cost, so the model is optimal exactly when this
+    /// is equal to the cost.
+    pub bound: i64,
+    /// The values of the requested tensors in the returned model.
+    pub models: Vec<Tensor<bool>>,
+}
+
+impl Optimum {
+    /// Returns true if the returned model is proven to be optimal.
+    pub fn is_optimal(&self) -> bool {
+        self.cost == self.bound
+    }
+}
+
+/// The best model found by a lexicographic optimization.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexOptimum {
+    /// The values of the objectives for the returned model.
+    pub costs: Vec<i64>,
+    /// The number of leading objectives whose values are proven optimal
+    /// in the lexicographic order.
+    pub num_optimal: usize,
+    /// The values of the requested tensors in the returned model.
+    pub models: Vec<Tensor<bool>>,
+}
+
+impl LexOptimum {
+    /// Returns true if the returned model is proven to be optimal for all
+    /// objectives.
+    pub fn is_optimal(&self) -> bool {
+        self.num_optimal == self.costs.len()
+    }
+}
+
+/// The result of a minimization over a boolean algebra, where the first
+/// values belong to the terms of the objective.
+pub(super) struct BoolOptimum {
+    pub cost: i64,
+    pub lower_bound: i64,
+    pub values: GenVec<bool>,
+}
+
+/// The result of a lexicographic minimization over a boolean algebra.
+pub(super) struct BoolLexOptimum {
+    pub costs: Vec<i64>,
+    pub num_optimal: usize,
+    pub values: GenVec<bool>,
+}
+
+/// Common state of the minimization algorithms.
+struct Minimizer<'a, ALG>
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+{
+    alg: &'a mut ALG,
+    assumptions: Vec<ALG::Elem>,
+    literals: Vec<ALG::Elem>,
+    terms: Vec<(ALG::Elem, i64)>,
+    offset: i64,
+    limits: Limits,
+    deadline: Option<Instant>,
+}
+
+impl<'a, ALG> Minimizer<'a, ALG>
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+{
+    fn new(
+        alg: &'a mut ALG,
+        assumptions: Vec<ALG::Elem>,
+        terms: Vec<(ALG::Elem, i64)>,
+        literals: Vec<ALG::Elem>,
+        limits: &Limits,
+        deadline: Option<Instant>,
+    ) -> Self {
+        // make all weights positive by negating the terms
+        let mut offset = 0;
+        let terms = terms
+            .into_iter()
+            .filter(|(_, w)| *w != 0)
+            .map(|(elem, weight)| {
+                if weight > 0 {
+                    (elem, weight)
+                } else {
+                    offset += weight;
+                    (alg.bool_not(elem), -weight)
+                }
+            })
+            .collect();
+
+        Minimizer {
+            alg,
+            assumptions,
+            literals,
+            terms,
+            offset,
+            limits: *limits,
+            deadline,
+        }
+    }
+
+    fn run(&mut self, strategy: Strategy) -> Result<Option<BoolOptimum>, Interrupted> {
+        match strategy {
+            Strategy::Linear => self.linear(),
+            Strategy::Binary => self.binary(),
+            Strategy::CoreGuided => self.core_guided(),
+        }
+    }
+    /// Solves the problem with the given extra assumptions and returns the
+    /// normalized cost and the values of the literals.
+    fn solve(&mut self, extra: &[ALG::Elem]) -> Result<Option<(i64, GenVec<bool>)>, Interrupted> {
+        let mut limits = self.limits;
+        if let Some(deadline) = self.deadline {
+            limits.time = Some(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        let mut assumptions = self.assumptions.clone();
+        assumptions.extend(extra.iter().cloned());
+        let literals = self.terms.iter().map(|(e, _)| *e);
+        let literals = literals.chain(self.literals.iter().cloned());
+        let values = match self
+            .alg
+            .bool_find_one_model_limited(&assumptions, literals, &limits)?
+        {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+
+        let cost = self
+            .terms
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| values.get(*i))
+            .map(|(_, (_, w))| w)
+            .sum();
+        Ok(Some((cost, values)))
+    }
+
+    fn result(&self, cost: i64, lower_bound: i64, values: GenVec<bool>) -> BoolOptimum {
+        BoolOptimum {
+            cost: cost + self.offset,
+            lower_bound: lower_bound + self.offset,
+            values: values.iter().skip(self.terms.len()).collect(),
+        }
+    }
+
+    /// Returns the predicate that the normalized cost is at most the bound.
+    fn atmost(&mut self, bound: i64) -> ALG::Elem {
+        let terms = self.terms.iter().cloned();
+        self.alg
+            .bool_fold_pb_atmost(terms, bound, PbEncoding::default())
+    }
+
+    fn linear(&mut self) -> Result<Option<BoolOptimum>, Interrupted> {
+        let (mut cost, mut values) = match self.solve(&[])? {
+            Some(model) => model,
+            None => return Ok(None),
+        };
+
+        while cost > 0 {
+            let bound = self.atmost(cost - 1);
+            match self.solve(&[bound]) {
+                Ok(Some(model)) => {
+                    cost = model.0;
+                    values = model.1;
+                }
+                Ok(None) => break,
+                Err(_) => return Ok(Some(self.result(cost, 0, values))),
+            }
+        }
+        Ok(Some(self.result(cost, cost, values)))
+    }
+
+    fn binary(&mut self) -> Result<Option<BoolOptimum>, Interrupted> {
+        let (mut cost, mut values) = match self.solve(&[])? {
+            Some(model) => model,
+            None => return Ok(None),
+        };
+
+        let mut lower_bound = 0;
+        while lower_bound < cost {
+            let middle = lower_bound + (cost - lower_bound) / 2;
+            let bound = self.atmost(middle);
+            match self.solve(&[bound]) {
+                Ok(Some(model)) => {
+                    cost = model.0;
+                    values = model.1;
+                }
+                Ok(None) => lower_bound = middle + 1,
+                Err(_) => break,
+            }
+        }
+        Ok(Some(self.result(cost, lower_bound, values)))
+    }
+
+    fn core_guided(&mut self) -> Result<Option<BoolOptimum>, Interrupted> {
+        let (mut cost, mut values) = match self.solve(&[])? {
+            Some(model) => model,
+            None => return Ok(None),
+        };
+
+        // every soft literal should be false, otherwise its weight is paid
+        let mut softs = self.terms.clone();
+        let mut lower_bound = 0;
+        while lower_bound < cost {
+            let active: Vec<usize> = (0..softs.len()).filter(|&i| softs[i].1 > 0).collect();
+            let extra: Vec<ALG::Elem> = active
+                .iter()
+                .map(|&i| self.alg.bool_not(softs[i].0))
+                .collect();
+            match self.solve(&extra) {
+                Ok(Some(model)) => {
+                    cost = model.0;
+                    values = model.1;
+                    break;
+                }
+                Ok(None) => {}
+                Err(_) => break,
+            }
+
+            let all = self.assumptions.iter().cloned().chain(extra);
+            let failed = self.alg.bool_failed_assumptions(all);
+            let mut core: Vec<usize> = active
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| failed.get(self.assumptions.len() + j))
+                .map(|(_, &i)| i)
+                .collect();
+
+            // backends without real failed assumptions may report no core,
+            // then all active soft literals form a core if the hard
+            // constraints are still consistent
+            if core.is_empty() {
+                match self.solve(&[]) {
+                    Ok(Some(_)) => core = active,
+                    Ok(None) => return Ok(None),
+                    Err(_) => break,
+                }
+            }
+            let weight = core.iter().map(|&i| softs[i].1).min().unwrap();
+            lower_bound += weight;
+            for &i in core.iter() {
+                softs[i].1 -= weight;
+            }
+
+            // relax the core by paying for each additional true literal
+            let elems = core.iter().map(|&i| softs[i].0);
+            let counts = self
+                .alg
+                .bool_fold_count(elems, core.len(), CardEncoding::default());
+            for elem in counts.into_iter().skip(1) {
+                softs.push((elem, weight));
+            }
+        }
+        Ok(Some(self.result(cost, lower_bound, values)))
+    }
+}
+
+/// Minimizes the sum of the weights of the true terms under the given
+/// assumptions, and returns the values of the literals in the best model
+/// found. If the resources run out before a model is found, then an error
+/// is returned, otherwise the best model found so far is returned with the
+/// proven lower bound.
+pub(super) fn minimize<ALG, ITER>(
+    alg: &mut ALG,
+    assumptions: &[ALG::Elem],
+    terms: Vec<(ALG::Elem, i64)>,
+    literals: ITER,
+    strategy: Strategy,
+    limits: &Limits,
+) -> Result<Option<BoolOptimum>, Interrupted>
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+    ITER: Iterator<Item = ALG::Elem>,
+{
+    let deadline = limits.time.map(|time| Instant::now() + time);
+    let assumptions = assumptions.to_vec();
+    let literals = literals.collect();
+    Minimizer::new(alg, assumptions, terms, literals, limits, deadline).run(strategy)
+}
+
+/// Minimizes the objectives one after the other, keeping the optimal value
+/// of the earlier ones with an assumption, and returns the values of the
+/// literals in the best model found. If the resources run out, then the
+/// best model of the current objective is returned.
+pub(super) fn minimize_lex<ALG, ITER>(
+    alg: &mut ALG,
+    assumptions: &[ALG::Elem],
+    objectives: Vec<Vec<(ALG::Elem, i64)>>,
+    literals: ITER,
+    strategy: Strategy,
+    limits: &Limits,
+) -> Result<Option<BoolLexOptimum>, Interrupted>
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+    ITER: Iterator<Item = ALG::Elem>,
+{
+    assert!(!objectives.is_empty());
+    let deadline = limits.time.map(|time| Instant::now() + time);
+    let mut assumptions = assumptions.to_vec();
+    let mut all_literals: Vec<ALG::Elem> = objectives.iter().flatten().map(|(e, _)| *e).collect();
+    let num_objective_literals = all_literals.len();
+    all_literals.extend(literals);
+
+    let mut best = None;
+    let mut num_optimal = 0;
+    for terms in objectives.iter() {
+        let mut minimizer = Minimizer::new(
+            alg,
+            assumptions.clone(),
+            terms.clone(),
+            all_literals.clone(),
+            limits,
+            deadline,
+        );
+        let result = match minimizer.run(strategy) {
+            Ok(Some(result)) => result,
+            Ok(None) => return Ok(None),
+            Err(err) if best.is_none() => return Err(err),
+            Err(_) => break,
+        };
+
+        let optimal = result.cost == result.lower_bound;
+        best = Some(result.values);
+        if !optimal {
+            break;
+        }
+        num_optimal += 1;
+        let terms = terms.iter().copied();
+        let bound = alg.bool_fold_pb_atmost(terms, result.cost, PbEncoding::default());
+        assumptions.push(bound);
+    }
+
+    let values = best.unwrap();
+    let mut pos = 0;
+    let mut costs = Vec::with_capacity(objectives.len());
+    for terms in objectives.iter() {
+        let cost = terms
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| values.get(pos + i))
+            .map(|(_, (_, w))| w)
+            .sum();
+        costs.push(cost);
+        pos += terms.len();
+    }
+
+    Ok(Some(BoolLexOptimum {
+        costs,
+        num_optimal,
+        values: values.iter().skip(num_objective_literals).collect(),
+    }))
+}
+
+/// Finds a model where the set of true elements is minimal with respect to
+/// inclusion, and returns the values of the literals in it.
+pub(super) fn find_minimal_model<ALG, ITER>(
+    alg: &mut ALG,
+    assumptions: &[ALG::Elem],
+    elems: Vec<ALG::Elem>,
+    literals: ITER,
+    limits: &Limits,
+) -> Result<Option<GenVec<bool>>, Interrupted>
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+    ITER: Iterator<Item = ALG::Elem>,
+{
+    let deadline = limits.time.map(|time| Instant::now() + time);
+    let terms = elems.into_iter().map(|elem| (elem, 1)).collect();
+    let assumptions = assumptions.to_vec();
+    let literals = literals.collect();
+    let mut minimizer = Minimizer::new(alg, assumptions, terms, literals, limits, deadline);
+
+    let mut values = match minimizer.solve(&[])? {
+        Some((_, values)) => values,
+        None => return Ok(None),
+    };
+    loop {
+        // keep the false elements false and make one of the true ones false
+        let mut extra = Vec::new();
+        let mut shrink = Vec::new();
+        for (i, (elem, _)) in minimizer.terms.iter().enumerate() {
+            let elem = minimizer.alg.bool_not(*elem);
+            if values.get(i) {
+                shrink.push(elem);
+            } else {
+                extra.push(elem);
+            }
+        }
+        if shrink.is_empty() {
+            break;
+        }
+        extra.push(minimizer.alg.bool_fold_any(shrink.into_iter()));
+
+        match minimizer.solve(&extra)? {
+            Some((_, model)) => values = model,
+            None => break,
+        }
+    }
+    Ok(Some(values.iter().skip(minimizer.terms.len()).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        create_solver, Boolean, Literal, SatSolver, Shape, SolveResult, Solver, TensorAlg,
+        TensorSat,
+    };
+    use super::*;
+
+    #[test]
+    fn minimize() {
+        for strategy in [Strategy::Linear, Strategy::Binary, Strategy::CoreGuided] {
+            // a 4 by 4 matrix with at least two true entries in each row
+            // and column, where the main diagonal is more expensive
+            let mut alg = Solver::new("");
+            let shape = Shape::new(vec![4, 4]);
+            let t1 = alg.tensor_add_variable(shape.clone());
+            let t2 = alg.tensor_atleast(t1.clone(), 2, Default::default());
+            let t2 = alg.tensor_all(t2);
+            alg.tensor_add_clause1(t2);
+            let t2 = alg.tensor_polymer(t1.clone(), shape.clone(), &[1, 0]);
+            let t2 = alg.tensor_atleast(t2, 2, Default::default());
+            let t2 = alg.tensor_all(t2);
+            alg.tensor_add_clause1(t2);
+
+            let weights = Tensor::create(shape.clone(), |c| if c[0] == c[1] { 3 } else { 1 });
+            let limits = Default::default();
+            let elems = std::slice::from_ref(&t1);
+            let result = alg
+                .tensor_minimize(&[], t1.clone(), weights.clone(), elems, strategy, &limits)
+                .unwrap()
+                .unwrap();
+            assert!(result.is_optimal());
+            assert_eq!(result.cost, 8);
+            let model = result.models[0].clone();
+            let diag = Boolean().tensor_polymer(model, Shape::new(vec![4]), &[0, 0]);
+            let diag = Boolean().tensor_any(diag);
+            assert!(!diag.scalar());
+
+            let result = alg
+                .tensor_maximize(&[], t1.clone(), weights, elems, strategy, &limits)
+                .unwrap()
+                .unwrap();
+            assert_eq!((result.cost, result.bound), (24, 24));
+        }
+    }
+
+    /// A solver that does not report the failed assumptions.
+    struct NoCore(Box<dyn SatSolver>);
+
+    impl SatSolver for NoCore {
+        fn add_variable(&mut self) -> Literal {
+            self.0.add_variable()
+        }
+
+        fn negate(&self, lit: Literal) -> Literal {
+            self.0.negate(lit)
+        }
+
+        fn add_clause(&mut self, lits: &[Literal]) {
+            self.0.add_clause(lits)
+        }
+
+        fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+            self.0.solve_limited(lits, limits)
+        }
+
+        fn get_value(&self, lit: Literal) -> bool {
+            self.0.get_value(lit)
+        }
+
+        fn failed_assumptions(&self) -> Vec<Literal> {
+            Vec::new()
+        }
+
+        fn get_name(&self) -> &'static str {
+            "NoCore"
+        }
+
+        fn num_variables(&self) -> u32 {
+            self.0.num_variables()
+        }
+
+        fn num_clauses(&self) -> usize {
+            self.0.num_clauses()
+        }
+    }
+
+    #[test]
+    fn minimize_without_core() {
+        let mut alg = Solver::with_solver(Box::new(NoCore(create_solver(""))));
+        let t1 = alg.tensor_add_variable(Shape::new(vec![4]));
+        let t2 = alg.tensor_atleast(t1.clone(), 2, Default::default());
+        alg.tensor_add_clause1(t2);
+
+        let weights = Tensor::create(Shape::new(vec![4]), |c| c[0] as i64 + 1);
+        let limits = Default::default();
+        let result = alg
+            .tensor_minimize(&[], t1, weights, &[], Strategy::CoreGuided, &limits)
+            .unwrap()
+            .unwrap();
+        assert!(result.is_optimal());
+        assert_eq!(result.cost, 3);
+    }
+
+    #[test]
+    fn minimal_model() {
+        let mut alg = Solver::new("");
+        let t1 = alg.tensor_add_variable(Shape::new(vec![5]));
+        let t2 = alg.tensor_atleast(t1.clone(), 2, Default::default());
+        alg.tensor_add_clause1(t2);
+
+        let limits = Default::default();
+        let elems = std::slice::from_ref(&t1);
+        let mut models = alg
+            .tensor_find_minimal_model(&[], t1.clone(), elems, &limits)
+            .unwrap()
+            .unwrap();
+        let model = models.pop().unwrap();
+        let count = Boolean().tensor_exactly(model, 2, Default::default());
+        assert!(count.scalar());
+    }
+
+    #[test]
+    fn minimize_lex() {
+        // at least four of the six entries are true
+        let mut alg = Solver::new("");
+        let t1 = alg.tensor_add_variable(Shape::new(vec![3]));
+        let t2 = alg.tensor_add_variable(Shape::new(vec![3]));
+        let t3 = alg.tensor_polymer(t1.clone(), Shape::new(vec![3, 2]), &[0]);
+        let t4 = alg.tensor_polymer(t2.clone(), Shape::new(vec![3, 2]), &[0]);
+        let t5 = alg.tensor_create(Shape::new(vec![3, 2]), |c| c[1] == 0);
+        let t3 = alg.tensor_and(t3, t5.clone());
+        let t5 = alg.tensor_not(t5);
+        let t4 = alg.tensor_and(t4, t5);
+        let t3 = alg.tensor_or(t3, t4);
+        let t3 = alg.tensor_reshape(t3, Shape::new(vec![6]));
+        let t3 = alg.tensor_atleast(t3, 4, Default::default());
+        alg.tensor_add_clause1(t3);
+
+        let weights = Tensor::create(Shape::new(vec![3]), |c| c[0] as i64 + 1);
+        let ones = Tensor::create(Shape::new(vec![3]), |_| 1);
+        let limits = Default::default();
+        let elems = std::slice::from_ref(&t2);
+        for strategy in [Strategy::Linear, Strategy::Binary, Strategy::CoreGuided] {
+            let objectives = [(t1.clone(), ones.clone()), (t2.clone(), weights.clone())];
+            let result = alg
+                .tensor_minimize_lex(&[], &objectives, elems, strategy, &limits)
+                .unwrap()
+                .unwrap();
+            assert!(result.is_optimal());
+            assert_eq!(result.costs, vec![1, 6]);
+
+            let objectives = [(t2.clone(), weights.clone()), (t1.clone(), ones.clone())];
+            let result = alg
+                .tensor_minimize_lex(&[], &objectives, elems, strategy, &limits)
+                .unwrap()
+                .unwrap();
+            assert!(result.is_optimal());
+            assert_eq!(result.costs, vec![1, 3]);
+            assert_eq!(
+                result.models[0],
+                Tensor::create(Shape::new(vec![3]), |c| c[0] == 0)
+            );
+        }
+    }
+}
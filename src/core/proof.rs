@@ -0,0 +1,485 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Proofs of unsatisfiability in the DRAT format and a simple checker.
+
+use std::collections::HashMap;
+use std::{error, fmt, io, mem};
+
+use super::{DimacsError, Formula};
+
+/// A single step of a clausal proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProofStep<LIT> {
+    /// Adds a clause implied by the current set of clauses.
+    Add(Vec<LIT>),
+    /// Deletes a clause that is no longer needed.
+    Delete(Vec<LIT>),
+}
+
+impl<LIT> ProofStep<LIT> {
+    /// Returns the same step with each literal replaced by the given map.
+    pub fn map<LIT2, F>(self, fun: F) -> ProofStep<LIT2>
+    where
+        F: FnMut(LIT) -> LIT2,
+    {
+        match self {
+            ProofStep::Add(clause) => ProofStep::Add(clause.into_iter().map(fun).collect()),
+            ProofStep::Delete(clause) => ProofStep::Delete(clause.into_iter().map(fun).collect()),
+        }
+    }
+}
+
+/// Error type for reporting invalid proof steps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofError {
+    /// The index (starting from 1) of the failing step, one more than the
+    /// number of steps if the empty clause was not derived, or zero if the
+    /// solver could not log the proof.
+    pub step: usize,
+    /// The description of the problem.
+    pub msg: String,
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "step {}: {}", self.step, self.msg)
+    }
+}
+
+impl error::Error for ProofError {}
+
+/// The outcome of trying to prove that a formula is unsatisfiable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProofResult {
+    /// The formula is unsatisfiable as shown by the verified proof.
+    Unsat(Proof),
+    /// The formula has a model.
+    Sat,
+    /// The solver stopped before deciding the problem.
+    Unknown,
+}
+
+/// A clausal proof of unsatisfiability of a formula, where the literals
+/// follow the DIMACS convention of `Formula`.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Proof {
+    steps: Vec<ProofStep<i32>>,
+}
+
+impl Proof {
+    /// Creates a new empty proof.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends a new step to the proof.
+    pub fn add_step(&mut self, step: ProofStep<i32>) {
+        self.steps.push(step);
+    }
+
+    /// Returns the steps of the proof.
+    pub fn steps(&self) -> &[ProofStep<i32>] {
+        &self.steps
+    }
+
+    /// Writes the proof in the textual DRAT format into the given output.
+    pub fn write_drat(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        for step in self.steps.iter() {
+            let clause = match step {
+                ProofStep::Add(clause) => clause,
+                ProofStep::Delete(clause) => {
+                    write!(out, "d ")?;
+                    clause
+                }
+            };
+            for lit in clause {
+                write!(out, "{} ", lit)?;
+            }
+            writeln!(out, "0")?;
+        }
+        Ok(())
+    }
+
+    /// Reads a proof in the textual DRAT format from the given input.
+    pub fn read_drat(input: &mut dyn io::BufRead) -> Result<Proof, DimacsError> {
+        let mut proof = Proof::new();
+        let mut clause: Vec<i32> = Vec::new();
+        let mut delete = false;
+        let mut line_num = 0;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            line_num += 1;
+            match input.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => return Err(DimacsError::new(line_num, err.to_string())),
+            }
+
+            let mut rest = line.trim();
+            if rest.is_empty() || rest.starts_with('c') {
+                continue;
+            }
+            if clause.is_empty() && !delete && rest.starts_with('d') {
+                delete = true;
+                rest = &rest[1..];
+            }
+
+            for token in rest.split_whitespace() {
+                let lit: i32 = match token.parse() {
+                    Ok(lit) if lit != i32::MIN => lit,
+                    _ => {
+                        let msg = format!("invalid literal {}", token);
+                        return Err(DimacsError::new(line_num, msg));
+                    }
+                };
+                if lit == 0 {
+                    let clause = mem::take(&mut clause);
+                    proof.steps.push(if delete {
+                        ProofStep::Delete(clause)
+                    } else {
+                        ProofStep::Add(clause)
+                    });
+                    delete = false;
+                } else {
+                    clause.push(lit);
+                }
+            }
+        }
+
+        if !clause.is_empty() || delete {
+            return Err(DimacsError::new(line_num, "unterminated clause".into()));
+        }
+        Ok(proof)
+    }
+
+    /// Checks that every added clause has the reverse unit propagation or
+    /// the resolution asymmetric tautology property with respect to the
+    /// clauses of the formula and the earlier steps, and that the empty
+    /// clause is derived. Deletions of unknown or unit clauses, and of clauses
+    /// that are the reasons of top level assignments, are ignored.
+    pub fn check(&self, formula: &Formula) -> Result<(), ProofError> {
+        let mut checker = Checker::default();
+        for clause in formula.clauses() {
+            checker.add_clause(clause);
+        }
+
+        // the fresh variables of long XOR clauses must not clash with the
+        // variables introduced by the proof
+        let mut num_variables = self
+            .steps
+            .iter()
+            .flat_map(|step| match step {
+                ProofStep::Add(clause) | ProofStep::Delete(clause) => clause.iter(),
+            })
+            .map(|lit| lit.unsigned_abs())
+            .fold(formula.num_variables(), u32::max);
+        for xor in formula.xor_clauses() {
+            Formula::expand_xor_clause(xor, &mut num_variables, |clause| {
+                checker.add_clause(clause)
+            });
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            match step {
+                ProofStep::Add(clause) => {
+                    if !checker.is_implied(clause) {
+                        return Err(ProofError {
+                            step: index + 1,
+                            msg: format!("clause {:?} is not implied", clause),
+                        });
+                    }
+                    checker.add_clause(clause);
+                }
+                ProofStep::Delete(clause) => checker.delete_clause(clause),
+            }
+        }
+
+        if checker.inconsistent {
+            Ok(())
+        } else {
+            Err(ProofError {
+                step: self.steps.len() + 1,
+                msg: "the empty clause is not derived".into(),
+            })
+        }
+    }
+}
+
+/// Unit propagation with two watched literals for checking proofs.
+#[derive(Default)]
+struct Checker {
+    clauses: Vec<Vec<i32>>,
+    active: Vec<bool>,
+    index: HashMap<Vec<i32>, Vec<usize>>,
+    watches: Vec<Vec<usize>>,
+    values: Vec<i8>,
+    trail: Vec<i32>,
+    head: usize,
+    inconsistent: bool,
+}
+
+impl Checker {
+    fn code(lit: i32) -> usize {
+        2 * (lit.unsigned_abs() as usize - 1) + (lit < 0) as usize
+    }
+
+    fn value(values: &[i8], lit: i32) -> i8 {
+        let value = values[lit.unsigned_abs() as usize - 1];
+        if lit < 0 {
+            -value
+        } else {
+            value
+        }
+    }
+
+    fn reserve(&mut self, lit: i32) {
+        let var = lit.unsigned_abs() as usize;
+        if self.values.len() < var {
+            self.values.resize(var, 0);
+            self.watches.resize(2 * var, Vec::new());
+        }
+    }
+
+    fn assign(&mut self, lit: i32) {
+        self.values[lit.unsigned_abs() as usize - 1] = if lit < 0 { -1 } else { 1 };
+        self.trail.push(lit);
+    }
+
+    /// Propagates the assigned literals and returns false on conflict.
+    fn propagate(&mut self) -> bool {
+        while self.head < self.trail.len() {
+            let false_lit = -self.trail[self.head];
+            self.head += 1;
+
+            let mut watches = mem::take(&mut self.watches[Checker::code(false_lit)]);
+            let mut conflict = false;
+            let mut pos = 0;
+            while pos < watches.len() {
+                let index = watches[pos];
+                if !self.active[index] {
+                    watches.swap_remove(pos);
+                    continue;
+                }
+
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if Checker::value(&self.values, first) > 0 {
+                    pos += 1;
+                    continue;
+                }
+
+                let values = &self.values;
+                let other = (2..clause.len()).find(|&k| Checker::value(values, clause[k]) >= 0);
+                if let Some(k) = other {
+                    clause.swap(1, k);
+                    self.watches[Checker::code(clause[1])].push(index);
+                    watches.swap_remove(pos);
+                } else if Checker::value(&self.values, first) < 0 {
+                    conflict = true;
+                    break;
+                } else {
+                    self.assign(first);
+                    pos += 1;
+                }
+            }
+
+            self.watches[Checker::code(false_lit)] = watches;
+            if conflict {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Removes the assignments made after the given length of the trail.
+    fn backtrack(&mut self, len: usize) {
+        for &lit in self.trail[len..].iter() {
+            self.values[lit.unsigned_abs() as usize - 1] = 0;
+        }
+        self.trail.truncate(len);
+        self.head = len;
+    }
+
+    fn add_clause(&mut self, clause: &[i32]) {
+        let mut clause = clause.to_vec();
+        clause.sort_unstable();
+        clause.dedup();
+        if self.inconsistent
+            || clause
+                .iter()
+                .any(|&lit| clause.binary_search(&-lit).is_ok())
+        {
+            return;
+        }
+        for &lit in clause.iter() {
+            self.reserve(lit);
+        }
+
+        let index = self.clauses.len();
+        self.index.entry(clause.clone()).or_default().push(index);
+
+        // move the non-false literals to the front
+        clause.sort_by_key(|&lit| -Checker::value(&self.values, lit));
+        let num_free = clause
+            .iter()
+            .filter(|&&lit| Checker::value(&self.values, lit) >= 0)
+            .count();
+        if clause.len() >= 2 {
+            self.watches[Checker::code(clause[0])].push(index);
+            self.watches[Checker::code(clause[1])].push(index);
+        }
+
+        if num_free == 0 {
+            self.inconsistent = true;
+        } else if num_free == 1 && Checker::value(&self.values, clause[0]) == 0 {
+            self.assign(clause[0]);
+            self.inconsistent = !self.propagate();
+        }
+
+        self.clauses.push(clause);
+        self.active.push(true);
+    }
+
+    fn delete_clause(&mut self, clause: &[i32]) {
+        let mut clause = clause.to_vec();
+        clause.sort_unstable();
+        clause.dedup();
+        if clause.len() <= 1 {
+            return;
+        }
+
+        // keep the clauses that may be the reasons of top level assignments
+        let values = &self.values;
+        let value = |lit: i32| {
+            if lit.unsigned_abs() as usize <= values.len() {
+                Checker::value(values, lit)
+            } else {
+                0
+            }
+        };
+        if clause.iter().filter(|&&lit| value(lit) > 0).count() == 1
+            && clause.iter().all(|&lit| value(lit) != 0)
+        {
+            return;
+        }
+
+        if let Some(indices) = self.index.get_mut(&clause) {
+            if let Some(index) = indices.pop() {
+                self.active[index] = false;
+            }
+        }
+    }
+
+    /// Checks if the clause has the reverse unit propagation property.
+    fn is_rup(&mut self, clause: &[i32]) -> bool {
+        if self.inconsistent {
+            return true;
+        }
+        for &lit in clause {
+            self.reserve(lit);
+        }
+
+        let len = self.trail.len();
+        let mut conflict = false;
+        for &lit in clause {
+            match Checker::value(&self.values, lit) {
+                0 => self.assign(-lit),
+                1 => {
+                    conflict = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        if !conflict {
+            conflict = !self.propagate();
+        }
+        self.backtrack(len);
+        conflict
+    }
+
+    /// Checks if the clause has the reverse unit propagation property, or
+    /// the resolution asymmetric tautology property on its first literal.
+    fn is_implied(&mut self, clause: &[i32]) -> bool {
+        if self.is_rup(clause) {
+            return true;
+        }
+        let pivot = match clause.first() {
+            Some(&lit) => lit,
+            None => return false,
+        };
+
+        let candidates: Vec<usize> = (0..self.clauses.len())
+            .filter(|&i| self.active[i] && self.clauses[i].contains(&-pivot))
+            .collect();
+        for index in candidates {
+            let mut resolvent = clause.to_vec();
+            resolvent.extend(self.clauses[index].iter().filter(|&&lit| lit != -pivot));
+            if !resolvent.iter().any(|&lit| resolvent.contains(&-lit)) && !self.is_rup(&resolvent) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check() {
+        let text = "p cnf 2 4\n1 2 0\n-1 2 0\n1 -2 0\n-1 -2 0\n";
+        let formula = Formula::read_dimacs(&mut text.as_bytes()).unwrap();
+
+        let proof = Proof::read_drat(&mut "c comment\n1 0\nd 1 2 0\n0\n".as_bytes()).unwrap();
+        assert_eq!(proof.steps().len(), 3);
+        assert_eq!(proof.steps()[1], ProofStep::Delete(vec![1, 2]));
+        assert_eq!(proof.check(&formula), Ok(()));
+
+        let mut out: Vec<u8> = Vec::new();
+        proof.write_drat(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1 0\nd 1 2 0\n0\n");
+
+        let proof = Proof::read_drat(&mut "2 0\n".as_bytes()).unwrap();
+        assert_eq!(proof.check(&formula), Ok(()));
+        assert_eq!(Proof::new().check(&formula).unwrap_err().step, 1);
+
+        let proof = Proof::read_drat(&mut "d 1 2 0\n1 0\n".as_bytes()).unwrap();
+        assert_eq!(proof.check(&formula).unwrap_err().step, 2);
+
+        // deleting the reason of a top level assignment does not undo it
+        let text = "p cnf 2 2\n1 2 0\n-2 0\n";
+        let formula = Formula::read_dimacs(&mut text.as_bytes()).unwrap();
+        let proof = Proof::read_drat(&mut "d 1 2 0\n-1 0\n0\n".as_bytes()).unwrap();
+        assert_eq!(proof.check(&formula).unwrap_err().step, 2);
+
+        // adding a fresh variable equivalent to 1 has the RAT property
+        let text = "p cnf 1 0\n";
+        let formula = Formula::read_dimacs(&mut text.as_bytes()).unwrap();
+        let proof = Proof::read_drat(&mut "3 -1 0\n-3 1 0\n".as_bytes()).unwrap();
+        assert_eq!(proof.check(&formula).unwrap_err().step, 3);
+        let proof = Proof::read_drat(&mut "3 -1 0\n-3 1 0\n3 0\n0\n".as_bytes()).unwrap();
+        assert_eq!(proof.check(&formula).unwrap_err().step, 3);
+    }
+}
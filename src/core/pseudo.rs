@@ -0,0 +1,275 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Encodings of pseudo-boolean linear constraints with boolean gates.
+
+use super::{cardinality, BoolAlg, CardEncoding};
+
+/// The circuit used to compare a weighted sum against a bound.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PbEncoding {
+    /// Reduced ordered decision diagram over the terms sorted by weight.
+    #[default]
+    Bdd,
+    /// Binary adder network followed by a comparator.
+    Adder,
+    /// Sorting networks for each binary digit with carries between them.
+    Sorter,
+}
+
+/// Returns the predicate that the sum of the weights of the true elements
+/// is at most the given bound. The sum of the absolute values of the
+/// weights must fit into an `i64`.
+pub(super) fn atmost<ALG>(
+    alg: &mut ALG,
+    terms: Vec<(ALG::Elem, i64)>,
+    mut bound: i64,
+    encoding: PbEncoding,
+) -> ALG::Elem
+where
+    ALG: BoolAlg + ?Sized,
+{
+    // make all weights positive by negating the elements
+    let mut pos: Vec<(ALG::Elem, i64)> = Vec::with_capacity(terms.len());
+    for (elem, weight) in terms {
+        if weight > 0 {
+            pos.push((elem, weight));
+        } else if weight < 0 {
+            bound -= weight;
+            let elem = alg.bool_not(elem);
+            pos.push((elem, -weight));
+        }
+    }
+    if bound < 0 {
+        return alg.bool_zero();
+    }
+
+    // elements heavier than the bound must be false
+    let mut result = alg.bool_unit();
+    let mut terms = Vec::with_capacity(pos.len());
+    for (elem, weight) in pos {
+        if weight > bound {
+            let elem = alg.bool_not(elem);
+            result = alg.bool_and(result, elem);
+        } else {
+            terms.push((elem, weight));
+        }
+    }
+    if terms.iter().map(|(_, w)| w).sum::<i64>() <= bound {
+        return result;
+    }
+
+    let elem = match encoding {
+        PbEncoding::Bdd => bdd(alg, terms, bound),
+        PbEncoding::Adder => adder(alg, terms, bound),
+        PbEncoding::Sorter => sorter(alg, terms, bound),
+    };
+    alg.bool_and(result, elem)
+}
+
+fn bdd<ALG>(alg: &mut ALG, mut terms: Vec<(ALG::Elem, i64)>, bound: i64) -> ALG::Elem
+where
+    ALG: BoolAlg + ?Sized,
+{
+    terms.sort_by_key(|(_, w)| -w);
+    let mut suffix = vec![0; terms.len() + 1];
+    for i in (0..terms.len()).rev() {
+        suffix[i] = suffix[i + 1] + terms[i].1;
+    }
+
+    // every node is valid for an interval of bounds
+    let mut nodes: Vec<Vec<(i64, i64, ALG::Elem)>> = vec![Vec::new(); terms.len()];
+    bdd_node(alg, &terms, &suffix, &mut nodes, 0, bound).2
+}
+
+fn bdd_node<ALG>(
+    alg: &mut ALG,
+    terms: &[(ALG::Elem, i64)],
+    suffix: &[i64],
+    nodes: &mut Vec<Vec<(i64, i64, ALG::Elem)>>,
+    index: usize,
+    bound: i64,
+) -> (i64, i64, ALG::Elem)
+where
+    ALG: BoolAlg + ?Sized,
+{
+    if bound < 0 {
+        return (i64::MIN, -1, alg.bool_zero());
+    } else if suffix[index] <= bound {
+        return (suffix[index], i64::MAX, alg.bool_unit());
+    }
+    for node in nodes[index].iter() {
+        if node.0 <= bound && bound <= node.1 {
+            return node.clone();
+        }
+    }
+
+    let (elem, weight) = terms[index].clone();
+    let high = bdd_node(alg, terms, suffix, nodes, index + 1, bound - weight);
+    let low = bdd_node(alg, terms, suffix, nodes, index + 1, bound);
+    let min = high.0.saturating_add(weight).max(low.0);
+    let max = high.1.saturating_add(weight).min(low.1);
+
+    let tmp1 = alg.bool_and(elem.clone(), high.2);
+    let tmp2 = alg.bool_not(elem);
+    let tmp2 = alg.bool_and(tmp2, low.2);
+    let node = (min, max, alg.bool_or(tmp1, tmp2));
+    nodes[index].push(node.clone());
+    node
+}
+
+/// Returns the number of binary digits of the given positive bound.
+fn num_digits(bound: i64) -> usize {
+    (64 - bound.leading_zeros()) as usize
+}
+
+fn adder<ALG>(alg: &mut ALG, terms: Vec<(ALG::Elem, i64)>, bound: i64) -> ALG::Elem
+where
+    ALG: BoolAlg + ?Sized,
+{
+    let len = num_digits(bound);
+    let mut columns: Vec<Vec<ALG::Elem>> = vec![Vec::new(); len + 1];
+    for (elem, weight) in terms {
+        for (i, column) in columns.iter_mut().enumerate().take(len) {
+            if (weight >> i) & 1 != 0 {
+                column.push(elem.clone());
+            }
+        }
+    }
+
+    // full and half adders reduce each column to a single digit
+    let mut digits = Vec::with_capacity(len);
+    for i in 0..len {
+        let mut column = std::mem::take(&mut columns[i]);
+        while column.len() >= 2 {
+            let a = column.pop().unwrap();
+            let b = column.pop().unwrap();
+            if let Some(c) = column.pop() {
+                let carry = alg.bool_maj(a.clone(), b.clone(), c.clone());
+                columns[i + 1].push(carry);
+                let sum = alg.bool_sum3(a, b, c);
+                column.insert(0, sum);
+            } else {
+                let carry = alg.bool_and(a.clone(), b.clone());
+                columns[i + 1].push(carry);
+                let sum = alg.bool_xor(a, b);
+                column.push(sum);
+            }
+        }
+        digits.push(match column.pop() {
+            Some(elem) => elem,
+            None => alg.bool_zero(),
+        });
+    }
+
+    let overflow = std::mem::take(&mut columns[len]);
+    let overflow = alg.bool_fold_any(overflow.into_iter());
+    let overflow = alg.bool_not(overflow);
+    let pairs: Vec<(ALG::Elem, ALG::Elem)> = digits
+        .into_iter()
+        .enumerate()
+        .map(|(i, elem)| (elem, alg.bool_lift((bound >> i) & 1 != 0)))
+        .collect();
+    let result = alg.bool_cmp_leq(pairs.into_iter());
+    alg.bool_and(overflow, result)
+}
+
+fn sorter<ALG>(alg: &mut ALG, terms: Vec<(ALG::Elem, i64)>, bound: i64) -> ALG::Elem
+where
+    ALG: BoolAlg + ?Sized,
+{
+    // no weight is larger than the bound, so the top digit is at most one
+    // for each term and the top bucket counts in unary
+    let len = num_digits(bound);
+    let mut carries: Vec<ALG::Elem> = Vec::new();
+    let mut pairs: Vec<(ALG::Elem, ALG::Elem)> = Vec::with_capacity(len - 1);
+    for i in 0..len - 1 {
+        let mut bucket = std::mem::take(&mut carries);
+        for (elem, weight) in terms.iter() {
+            if (weight >> i) & 1 != 0 {
+                bucket.push(elem.clone());
+            }
+        }
+
+        let count = bucket.len();
+        let sorted = cardinality::count(alg, bucket, count, CardEncoding::Sorter);
+        let mut digit = alg.bool_zero();
+        for j in (0..count).step_by(2) {
+            let tmp = match sorted.get(j + 1) {
+                Some(next) => {
+                    let tmp = alg.bool_not(next.clone());
+                    alg.bool_and(sorted[j].clone(), tmp)
+                }
+                None => sorted[j].clone(),
+            };
+            digit = alg.bool_or(digit, tmp);
+        }
+        carries = sorted.into_iter().skip(1).step_by(2).collect();
+        pairs.push((digit, alg.bool_lift((bound >> i) & 1 != 0)));
+    }
+
+    let mut bucket = carries;
+    for (elem, weight) in terms {
+        if (weight >> (len - 1)) & 1 != 0 {
+            bucket.push(elem);
+        }
+    }
+    let top = cardinality::count(alg, bucket, 2, CardEncoding::Sorter);
+    let lower = alg.bool_cmp_leq(pairs.into_iter());
+    let equal = alg.bool_not(top[1].clone());
+    let equal = alg.bool_and(top[0].clone(), equal);
+    let equal = alg.bool_and(equal, lower);
+    let less = alg.bool_not(top[0].clone());
+    alg.bool_or(less, equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::random::Random;
+    use super::super::{BoolSat, Boolean, Solver};
+    use super::*;
+
+    #[test]
+    fn consistency() {
+        let mut random = Random::new(1);
+        for encoding in [PbEncoding::Bdd, PbEncoding::Adder, PbEncoding::Sorter] {
+            for _ in 0..30 {
+                let len = 1 + random.next_below(5);
+                let weights: Vec<i64> =
+                    (0..len).map(|_| random.next_below(13) as i64 - 4).collect();
+                let bound = random.next_below(20) as i64 - 5;
+
+                let mut count = 0;
+                for bits in 0..(1 << len) {
+                    let elems: Vec<bool> = (0..len).map(|i| (bits >> i) & 1 != 0).collect();
+                    let sum: i64 = (0..len).filter(|&i| elems[i]).map(|i| weights[i]).sum();
+                    let terms = elems.iter().copied().zip(weights.iter().copied());
+                    let value = atmost(&mut Boolean(), terms.collect(), bound, encoding);
+                    assert_eq!(value, sum <= bound);
+                    count += value as usize;
+                }
+
+                let mut alg = Solver::new("");
+                let elems: Vec<_> = (0..len).map(|_| alg.bool_add_variable()).collect();
+                let terms = elems.iter().copied().zip(weights.iter().copied());
+                let value = atmost(&mut alg, terms.collect(), bound, encoding);
+                alg.bool_add_clause(&[value]);
+                assert_eq!(alg.bool_find_num_models_method1(elems.into_iter()), count);
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A small seeded pseudo random number generator for randomized algorithms.
+
+/// The xorshift64* generator of Vigna, which is fast and good enough for
+/// choosing random hash functions. The same seed always produces the same
+/// sequence.
+#[derive(Clone, Debug)]
+pub(super) struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Creates a new generator from the given seed.
+    pub fn new(seed: u64) -> Self {
+        // the state must not be zero, so mix the seed with splitmix64
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        Random {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    /// Returns the next random 64-bit number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Returns a random boolean value.
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 != 0
+    }
+
+    /// Returns a random number less than the given positive bound.
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0);
+        let bound = bound as u64;
+        let limit = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    /// Moves a random selection of the given number of elements to the
+    /// front of the slice in random order.
+    pub fn partial_shuffle<ELEM>(&mut self, elems: &mut [ELEM], count: usize) {
+        for i in 0..count.min(elems.len()) {
+            let j = i + self.next_below(elems.len() - i);
+            elems.swap(i, j);
+        }
+    }
+}
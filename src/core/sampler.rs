@@ -0,0 +1,146 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Near-uniform sampling of models with random XOR hash functions.
+
+use super::approx::{bounded_models, Hash};
+use super::random::Random;
+use super::{BoolSat, GenElem, GenVec};
+
+/// The smallest accepted cell size. The thresholds follow UniGen2 with
+/// `kappa = 0.638`, where the pivot is `3 * e^(1/2) * (1 + 1 / kappa)^2`
+/// and the cells must have between `pivot / (1 + kappa)` and
+/// `1 + (1 + kappa) * pivot` models.
+const LOW_THRESHOLD: usize = 20;
+
+/// The largest accepted cell size.
+const HIGH_THRESHOLD: usize = 55;
+
+/// Returns the given number of near-uniform random samples of the models
+/// with respect to the given literals, or an empty list if there are no
+/// models at all. If there are only a few models, then they are enumerated
+/// and the samples are chosen uniformly. Otherwise the models are split
+/// into random cells by XOR constraints, the number of constraints is
+/// adjusted until a cell of acceptable size is found, and a random subset
+/// of that cell is returned as in UniGen2. The samples taken from the same
+/// cell are distinct, so consecutive samples are slightly correlated. The
+/// blocking clauses of each round are retired as in `bounded_models`, but
+/// every round leaves behind an activation variable and its hash rows.
+pub(super) fn sample<ALG>(
+    alg: &mut ALG,
+    literals: Vec<ALG::Elem>,
+    count: usize,
+    seed: u64,
+) -> Vec<GenVec<bool>>
+where
+    ALG: BoolSat,
+    ALG::Elem: GenElem,
+{
+    let mut random = Random::new(seed);
+    let mut samples: Vec<GenVec<bool>> = Vec::with_capacity(count);
+
+    let models = bounded_models(alg, &[], &literals, HIGH_THRESHOLD + 1);
+    if models.is_empty() {
+        return samples;
+    } else if models.len() <= HIGH_THRESHOLD {
+        while samples.len() < count {
+            let index = random.next_below(models.len());
+            samples.push(models[index].clone());
+        }
+        return samples;
+    }
+
+    // the number of rows is kept between the calls, since the good values
+    // are close to the logarithm of the number of models
+    let mut rows = 1;
+    while samples.len() < count {
+        let mut hash = Hash::new(&literals, &mut random);
+        let assumptions = hash.rows(alg, rows).to_vec();
+        let mut cell = bounded_models(alg, &assumptions, &literals, HIGH_THRESHOLD + 1);
+
+        if cell.len() > HIGH_THRESHOLD && rows < literals.len() {
+            rows += 1;
+        } else if cell.len() < LOW_THRESHOLD {
+            rows = (rows - 1).max(1);
+        } else {
+            let batch = LOW_THRESHOLD.min(count - samples.len());
+            random.partial_shuffle(&mut cell, batch);
+            cell.truncate(batch);
+            samples.extend(cell);
+        }
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{BoolAlg, GenVector as _, Solver};
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Returns the chi-square statistic of the samples against the uniform
+    /// distribution on the given number of models.
+    fn chi_square(samples: Vec<GenVec<bool>>, num_models: usize) -> f64 {
+        let mut counts: HashMap<Vec<bool>, usize> = HashMap::new();
+        let total = samples.len();
+        for sample in samples {
+            *counts.entry(sample.iter().collect()).or_insert(0) += 1;
+        }
+        assert!(counts.len() <= num_models);
+
+        let expected = total as f64 / num_models as f64;
+        let missing = (num_models - counts.len()) as f64 * expected;
+        counts.values().fold(missing, |sum, &count| {
+            let diff = count as f64 - expected;
+            sum + diff * diff / expected
+        })
+    }
+
+    fn sample_models(num_vars: usize, count: usize, seed: u64) -> Vec<GenVec<bool>> {
+        let mut alg = Solver::new("");
+        let elems: Vec<_> = (0..num_vars).map(|_| alg.bool_add_variable()).collect();
+        let clause = alg.bool_or(elems[0], elems[1]);
+        alg.bool_add_clause(&[clause]);
+        let samples = sample(&mut alg, elems, count, seed);
+        assert_eq!(samples.len(), count);
+        for sample in samples.iter() {
+            assert!(sample.get(0) || sample.get(1));
+        }
+        samples
+    }
+
+    #[test]
+    fn uniformity() {
+        // few models are enumerated, the critical value is for 11 degrees
+        // of freedom at significance level 0.001
+        let samples = sample_models(4, 240, 1);
+        assert!(chi_square(samples, 12) < 31.26);
+
+        // many models are hashed, the critical value is for 95 degrees of
+        // freedom at significance level 0.001
+        let samples = sample_models(7, 960, 2);
+        assert!(chi_square(samples, 96) < 143.3);
+
+        let mut alg = Solver::new("");
+        let a = alg.bool_add_variable();
+        let b = alg.bool_not(a);
+        alg.bool_add_clause(&[a, b]);
+        alg.bool_add_clause(&[a]);
+        alg.bool_add_clause(&[b]);
+        assert!(sample(&mut alg, vec![a], 10, 3).is_empty());
+    }
+}
@@ -24,12 +24,202 @@ use batsat::SolverInterface as _;
 #[cfg(feature = "varisat")]
 use varisat::ExtendFormula as _;
 
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead as _, Read as _, Write as _};
+use std::process::{Command, Stdio};
+#[cfg(feature = "cadical")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[cfg(any(feature = "cadical", feature = "batsat"))]
+use super::set_progress;
+use super::{DimacsOptions, Formula, ProofStep};
+
 /// Uniform literal to allow runtime solver selection.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Literal {
     pub value: u32,
 }
 
+/// The outcome of running a SAT solver.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SolveResult {
+    /// A model satisfying all clauses and assumptions was found.
+    Sat,
+    /// There is no model satisfying all clauses and assumptions.
+    Unsat,
+    /// The solver stopped before deciding the problem.
+    Unknown,
+}
+
+/// Resource limits for a single call of the solver. Each backend honours
+/// the limits it supports and ignores the rest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Limits {
+    /// The wall clock time the solver can run.
+    pub time: Option<Duration>,
+    /// The number of conflicts the solver can encounter.
+    pub conflicts: Option<u64>,
+    /// The number of literals the solver can propagate.
+    pub propagations: Option<u64>,
+}
+
+/// Statistics collected by a SAT solver. The counters that the backend
+/// does not keep track of are `None`. The number of calls and the time
+/// spent in them are maintained by `Solver`, the backends report zero.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Statistics {
+    /// The number of solver calls.
+    pub calls: u64,
+    /// The total wall clock time spent in the solver calls.
+    pub time: Duration,
+    /// The number of conflicts found during the search.
+    pub conflicts: Option<u64>,
+    /// The number of decisions made during the search.
+    pub decisions: Option<u64>,
+    /// The number of literals propagated during the search.
+    pub propagations: Option<u64>,
+    /// The number of restarts of the search.
+    pub restarts: Option<u64>,
+    /// The number of clauses learnt during the search.
+    pub learnt_clauses: Option<u64>,
+}
+
+impl Statistics {
+    /// Returns the statistics collected since the given earlier ones.
+    pub fn since(&self, earlier: &Statistics) -> Statistics {
+        let diff = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.saturating_sub(b)),
+            _ => None,
+        };
+        Statistics {
+            calls: self.calls.saturating_sub(earlier.calls),
+            time: self.time.saturating_sub(earlier.time),
+            conflicts: diff(self.conflicts, earlier.conflicts),
+            decisions: diff(self.decisions, earlier.decisions),
+            propagations: diff(self.propagations, earlier.propagations),
+            restarts: diff(self.restarts, earlier.restarts),
+            learnt_clauses: diff(self.learnt_clauses, earlier.learnt_clauses),
+        }
+    }
+}
+
+impl std::fmt::Display for Statistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "calls={}, time={:.3}s",
+            self.calls,
+            self.time.as_secs_f64()
+        )?;
+        let counters = [
+            ("conflicts", self.conflicts),
+            ("decisions", self.decisions),
+            ("propagations", self.propagations),
+            ("restarts", self.restarts),
+            ("learnt_clauses", self.learnt_clauses),
+        ];
+        for (name, value) in counters.iter() {
+            if let Some(value) = value {
+                write!(f, ", {}={}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of the progress variable holding the number of learnt clauses.
+#[cfg(any(feature = "cadical", feature = "batsat"))]
+const LEARNT_PROGRESS: &str = "sat_learnt";
+
+/// The number of learnt clauses between two updates of the progress.
+#[cfg(any(feature = "cadical", feature = "batsat"))]
+const LEARNT_PERIOD: u64 = 1024;
+
+/// Error returned when a search was stopped before it could be completed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the solver was interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+impl Limits {
+    /// Returns the point in time when the solver must stop.
+    fn deadline(&self) -> Option<Instant> {
+        self.time.map(|time| Instant::now() + time)
+    }
+}
+
+/// A handle to stop a running solver from another thread, for example from
+/// a Ctrl-C handler. The request is sticky: once interrupted, every call of
+/// the solver returns `SolveResult::Unknown` until the handle is cleared.
+#[derive(Clone, Debug, Default)]
+pub struct Interrupt {
+    flag: Arc<AtomicBool>,
+}
+
+impl Interrupt {
+    /// Requests the solver to stop as soon as possible.
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Allows the solver to run again after an interrupt.
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns true if the solver was asked to stop.
+    pub fn is_interrupted(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if the handle was passed out from the solver.
+    #[cfg(any(feature = "minisat", feature = "cryptominisat"))]
+    fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.flag) > 1
+    }
+
+    /// Runs the given solver call. If the handle was passed out, then a
+    /// watcher thread calls the stop function when an interrupt arrives.
+    #[cfg(any(feature = "minisat", feature = "cryptominisat"))]
+    fn forward<STOP, RUN, RESULT>(&self, stop: STOP, run: RUN) -> RESULT
+    where
+        STOP: Fn() + Send,
+        RUN: FnOnce() -> RESULT,
+    {
+        if !self.is_shared() {
+            return run();
+        }
+
+        let done = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let watcher = scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    if self.is_interrupted() {
+                        stop();
+                        break;
+                    }
+                    std::thread::park_timeout(Duration::from_millis(10));
+                }
+            });
+            let result = run();
+            done.store(true, Ordering::Relaxed);
+            watcher.thread().unpark();
+            result
+        })
+    }
+}
+
 /// Generic SAT solver interface
 pub trait SatSolver {
     /// Adds a fresh variable to the solver.
@@ -53,18 +243,30 @@ pub trait SatSolver {
         self.add_clause(&[not_lit1, not_lit2, not_lit3]);
     }
 
-    /// Runs the solver and returns true if a solution is available.
-    fn solve(&mut self) -> bool {
+    /// Runs the solver and returns whether a solution is available.
+    fn solve(&mut self) -> SolveResult {
         self.solve_with(&[])
     }
 
     /// Runs the solver with the given assumptions and finds a model satisfying
-    /// all requirements. Returns false is no solution was found.
-    fn solve_with(&mut self, lits: &[Literal]) -> bool;
+    /// all requirements.
+    fn solve_with(&mut self, lits: &[Literal]) -> SolveResult {
+        self.solve_limited(lits, &Default::default())
+    }
+
+    /// Runs the solver with the given assumptions and resource limits. The
+    /// result is `SolveResult::Unknown` if the solver ran out of resources.
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult;
 
     /// Returns the value of the literal in the found model.
     fn get_value(&self, lit: Literal) -> bool;
 
+    /// Returns those assumptions of the last solver call that were used to
+    /// derive the unsatisfiability of the problem. This is meaningful only
+    /// if the last call returned `SolveResult::Unsat`. Solvers that cannot
+    /// tell which assumptions failed return all of them.
+    fn failed_assumptions(&self) -> Vec<Literal>;
+
     /// Returns the name of the solver
     fn get_name(&self) -> &'static str;
 
@@ -73,12 +275,91 @@ pub trait SatSolver {
 
     /// Returns the number of clauses in the solver.
     fn num_clauses(&self) -> usize;
+
+    /// Returns the formula with all variables and clauses added to the
+    /// solver, if this solver keeps track of them. The literals of the
+    /// formula are the literals of this solver.
+    fn get_formula(&self) -> Option<&Formula> {
+        None
+    }
+
+    /// Returns a handle that can interrupt this solver from another thread,
+    /// if this solver supports asynchronous interruption.
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        None
+    }
+
+    /// Starts logging the clauses learnt and deleted by the solver, which
+    /// form a clausal proof when the problem turns out unsatisfiable. This
+    /// must be called before the first solver call. Returns false if the
+    /// solver does not support proof logging or it was called too late.
+    fn enable_proof(&mut self) -> bool {
+        false
+    }
+
+    /// Returns the proof steps logged so far with the literals of this
+    /// solver, or `None` if proof logging is not enabled or the proof could
+    /// not be logged completely.
+    fn proof_steps(&mut self) -> Option<Vec<ProofStep<Literal>>> {
+        None
+    }
+
+    /// Returns the statistics collected by the solver since its creation.
+    fn statistics(&self) -> Statistics {
+        Default::default()
+    }
+
+    /// Starts or stops collecting the statistics that slow down the search,
+    /// which are not collected by default. Returns false if the solver has
+    /// no such statistics.
+    fn collect_statistics(&mut self, enable: bool) -> bool {
+        let _ = enable;
+        false
+    }
+
+    /// Starts or stops publishing the number of learnt clauses into the
+    /// `sat_learnt` progress variable while the solver is running. Returns
+    /// false if the solver does not support this.
+    fn publish_progress(&mut self, enable: bool) -> bool {
+        let _ = enable;
+        false
+    }
 }
 
 /// Tries to create a SAT solver with the given name. Currently "batsat",
 /// "varisat", "minisat" and "cryptominisat" are supported, but not on all
 /// platforms. Use the empty string to match the first available solver.
+/// The "record:" prefix wraps the named solver into a `Recorder`, while
+/// "external:" followed by a command line runs that program as an
+/// `External` solver, where arguments containing whitespace can be quoted
+/// with single or double quotes. The "portfolio" name runs all available
+/// CaDiCaL configurations, BatSat and VariSat in parallel as a `Portfolio`,
+/// and "portfolio:" followed by a comma separated list of names runs the
+/// listed solvers.
 pub fn create_solver(name: &str) -> Box<dyn SatSolver> {
+    if let Some(name) = name.strip_prefix("record:") {
+        return Box::new(Recorder::new(create_solver(name)));
+    } else if let Some(command) = name.strip_prefix("external:") {
+        let mut args = split_command(command).into_iter();
+        let program = args.next().expect("missing external solver command");
+        return Box::new(External::new(program, args));
+    } else if let Some(names) = name.strip_prefix("portfolio:") {
+        let names: Vec<&str> = names.split(',').collect();
+        return Box::new(Portfolio::new(&names));
+    } else if name == "portfolio" {
+        let mut names: Vec<&str> = Vec::new();
+        #[cfg(feature = "cadical")]
+        names.extend(["cadical", "cadical-sat", "cadical-unsat", "cadical-plain"]);
+        #[cfg(feature = "batsat")]
+        names.push("batsat");
+        #[cfg(feature = "varisat")]
+        names.push("varisat");
+        if names.is_empty() {
+            names.push("");
+        }
+        return Box::new(Portfolio::new(&names));
+    }
+
     #[cfg(feature = "batsat")]
     {
         if name == "batsat" || name.is_empty() {
@@ -147,6 +428,7 @@ impl std::fmt::Debug for dyn SatSolver {
 #[cfg(feature = "minisat")]
 pub struct MiniSat {
     ptr: *mut minisat::sys::minisat_solver_t,
+    interrupt: Interrupt,
 }
 
 #[cfg(feature = "minisat")]
@@ -154,7 +436,10 @@ impl Default for MiniSat {
     fn default() -> Self {
         let ptr = unsafe { minisat::sys::minisat_new() };
         unsafe { minisat::sys::minisat_eliminate(ptr, 1) };
-        MiniSat { ptr }
+        MiniSat {
+            ptr,
+            interrupt: Default::default(),
+        }
     }
 }
 
@@ -164,6 +449,27 @@ impl MiniSat {
         lbool > 0
     }
 
+    fn budget(limit: u64) -> i32 {
+        limit.min(i32::MAX as u64) as i32
+    }
+
+    /// Finishes the started solve call. If the interrupt handle was passed
+    /// out, then a watcher thread forwards the interrupt to the solver.
+    fn limited_solve_commit(&mut self) -> i32 {
+        let ptr = self.ptr;
+        let addr = ptr as usize;
+        self.interrupt.forward(
+            || {
+                let ptr = addr as *mut minisat::sys::minisat_solver_t;
+                unsafe { minisat::sys::minisat_interrupt(ptr) };
+            },
+            || unsafe {
+                minisat::sys::minisat_clearInterrupt(ptr);
+                minisat::sys::minisat_limited_solve_commit(ptr)
+            },
+        )
+    }
+
     fn encode(value: i32) -> Literal {
         Literal {
             value: value as u32,
@@ -193,12 +499,33 @@ impl SatSolver for MiniSat {
         unsafe { minisat::sys::minisat_addClause_commit(self.ptr) };
     }
 
-    fn solve_with(&mut self, lits: &[Literal]) -> bool {
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+        if self.interrupt.is_interrupted() {
+            return SolveResult::Unknown;
+        }
+
+        unsafe { minisat::sys::minisat_no_budget(self.ptr) };
+        if let Some(conflicts) = limits.conflicts {
+            let budget = MiniSat::budget(conflicts);
+            unsafe { minisat::sys::minisat_set_conf_budget(self.ptr, budget) };
+        }
+        if let Some(propagations) = limits.propagations {
+            let budget = MiniSat::budget(propagations);
+            unsafe { minisat::sys::minisat_set_prop_budget(self.ptr, budget) };
+        }
+
         unsafe { minisat::sys::minisat_solve_begin(self.ptr) };
         for lit in lits {
             unsafe { minisat::sys::minisat_solve_addLit(self.ptr, MiniSat::decode(*lit)) };
         }
-        MiniSat::is_true(unsafe { minisat::sys::minisat_solve_commit(self.ptr) })
+        let result = self.limited_solve_commit();
+        if result == unsafe { minisat::sys::minisat_get_l_True() } {
+            SolveResult::Sat
+        } else if result == unsafe { minisat::sys::minisat_get_l_False() } {
+            SolveResult::Unsat
+        } else {
+            SolveResult::Unknown
+        }
     }
 
     fn get_value(&self, lit: Literal) -> bool {
@@ -207,6 +534,17 @@ impl SatSolver for MiniSat {
         })
     }
 
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        // the conflict clause contains the negated assumptions
+        let len = unsafe { minisat::sys::minisat_conflict_len(self.ptr) };
+        (0..len)
+            .map(|i| {
+                let lit = unsafe { minisat::sys::minisat_conflict_nthLit(self.ptr, i) };
+                MiniSat::encode(unsafe { minisat::sys::minisat_negate(lit) })
+            })
+            .collect()
+    }
+
     fn get_name(&self) -> &'static str {
         "MiniSat"
     }
@@ -218,6 +556,10 @@ impl SatSolver for MiniSat {
     fn num_clauses(&self) -> usize {
         unsafe { minisat::sys::minisat_num_clauses(self.ptr) as usize }
     }
+
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        Some(self.interrupt.clone())
+    }
 }
 
 #[cfg(feature = "minisat")]
@@ -227,6 +569,23 @@ impl Drop for MiniSat {
     }
 }
 
+/// A buffer receiving the proof written by VariSat.
+#[cfg(feature = "varisat")]
+#[derive(Clone, Default)]
+struct ProofBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+#[cfg(feature = "varisat")]
+impl std::io::Write for ProofBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 /// A modern SAT solver implemented in pure rust.
 #[cfg(feature = "varisat")]
 pub struct VariSat<'a> {
@@ -235,6 +594,10 @@ pub struct VariSat<'a> {
     solver: varisat::Solver<'a>,
     solution: bit_vec::BitVec,
     temp: Vec<varisat::Lit>,
+    assumed: bool,
+    solved: bool,
+    proof: Option<ProofBuffer>,
+    proof_steps: Option<Vec<ProofStep<Literal>>>,
 }
 
 #[cfg(feature = "varisat")]
@@ -246,6 +609,10 @@ impl<'a> Default for VariSat<'a> {
             solver: varisat::Solver::new(),
             solution: bit_vec::BitVec::new(),
             temp: Vec::new(),
+            assumed: false,
+            solved: false,
+            proof: None,
+            proof_steps: None,
         }
     }
 }
@@ -276,6 +643,14 @@ impl<'a> SatSolver for VariSat<'a> {
     }
 
     fn add_clause(&mut self, lits: &[Literal]) {
+        // the DRAT writer cannot log clauses added after a solver call
+        if self.solved && self.proof_steps.is_some() {
+            if self.proof.take().is_some() {
+                self.solver.close_proof().unwrap();
+            }
+            self.proof_steps = None;
+        }
+
         self.temp.clear();
         self.temp
             .extend(lits.iter().map(|lit| VariSat::decode(*lit)));
@@ -283,11 +658,16 @@ impl<'a> SatSolver for VariSat<'a> {
         self.num_clauses += 1;
     }
 
-    fn solve_with(&mut self, lits: &[Literal]) -> bool {
-        self.temp.clear();
-        self.temp
-            .extend(lits.iter().map(|lit| VariSat::decode(*lit)));
-        self.solver.assume(&self.temp);
+    fn solve_limited(&mut self, lits: &[Literal], _limits: &Limits) -> SolveResult {
+        // the DRAT writer panics on assumptions, even on empty ones
+        if !lits.is_empty() || self.assumed {
+            self.temp.clear();
+            self.temp
+                .extend(lits.iter().map(|lit| VariSat::decode(*lit)));
+            self.solver.assume(&self.temp);
+            self.assumed = !lits.is_empty();
+        }
+        self.solved = true;
 
         self.solution.truncate(0);
         let solvable = self.solver.solve().unwrap();
@@ -299,8 +679,10 @@ impl<'a> SatSolver for VariSat<'a> {
                     self.solution.set(var, true);
                 }
             }
+            SolveResult::Sat
+        } else {
+            SolveResult::Unsat
         }
-        solvable
     }
 
     fn get_value(&self, lit: Literal) -> bool {
@@ -309,6 +691,15 @@ impl<'a> SatSolver for VariSat<'a> {
         self.solution.get(var).unwrap() ^ lit.is_negative()
     }
 
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        self.solver
+            .failed_core()
+            .unwrap_or(&[])
+            .iter()
+            .map(|lit| VariSat::encode(*lit))
+            .collect()
+    }
+
     fn get_name(&self) -> &'static str {
         "VariSat"
     }
@@ -320,6 +711,34 @@ impl<'a> SatSolver for VariSat<'a> {
     fn num_clauses(&self) -> usize {
         self.num_clauses
     }
+
+    fn enable_proof(&mut self) -> bool {
+        if self.solved {
+            return false;
+        }
+        let buffer: ProofBuffer = Default::default();
+        self.solver
+            .write_proof(buffer.clone(), varisat::ProofFormat::Drat);
+        self.proof = Some(buffer);
+        self.proof_steps = Some(Vec::new());
+        true
+    }
+
+    fn proof_steps(&mut self) -> Option<Vec<ProofStep<Literal>>> {
+        // the proof is buffered inside the solver until it is closed
+        if let Some(buffer) = self.proof.take() {
+            self.solver.close_proof().unwrap();
+            let text = buffer.0.borrow();
+            let proof = super::Proof::read_drat(&mut text.as_slice()).unwrap();
+            if let Some(steps) = self.proof_steps.as_mut() {
+                steps.extend(proof.steps().iter().map(|step| {
+                    step.clone()
+                        .map(|lit| VariSat::encode(varisat::Lit::from_dimacs(lit as isize)))
+                }));
+            }
+        }
+        self.proof_steps.clone()
+    }
 }
 
 /// An advanced SAT solver supporting XOR clauses.
@@ -328,6 +747,7 @@ pub struct CryptoMiniSat {
     solver: cryptominisat::SatSolver,
     num_clauses: usize,
     temp: Vec<cryptominisat::Lit>,
+    interrupt: Interrupt,
 }
 
 #[cfg(feature = "cryptominisat")]
@@ -337,6 +757,7 @@ impl Default for CryptoMiniSat {
             solver: cryptominisat::SatSolver::new(),
             num_clauses: 0,
             temp: Vec::new(),
+            interrupt: Default::default(),
         }
     }
 }
@@ -383,17 +804,57 @@ impl SatSolver for CryptoMiniSat {
         self.solver.add_xor_literal_clause(&lits, false);
     }
 
-    fn solve_with(&mut self, lits: &[Literal]) -> bool {
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+        if self.interrupt.is_interrupted() {
+            return SolveResult::Unknown;
+        }
+
+        let time = limits.time.map_or(f64::MAX, |time| time.as_secs_f64());
+        self.solver.set_max_time(time);
+        let conflicts = limits
+            .conflicts
+            .map_or(i64::MAX, |c| c.min(i64::MAX as u64) as i64);
+        self.solver.set_max_confl(conflicts);
+
         self.temp.clear();
         self.temp
             .extend(lits.iter().map(|lit| CryptoMiniSat::decode(*lit)));
-        self.solver.solve_with_assumptions(&self.temp) == cryptominisat::Lbool::True
+        // both calls go through the same raw pointer, so no reference to
+        // the solver is alive while the other thread interrupts it
+        let ptr: *mut cryptominisat::SatSolver = &mut self.solver;
+        let addr = ptr as usize;
+        let temp = &self.temp;
+        let result = self.interrupt.forward(
+            || {
+                let ptr = addr as *const cryptominisat::SatSolver;
+                // SAFETY: interrupt_asap only sets an atomic flag of the
+                // library, which is safe to do while another thread solves
+                unsafe { (*ptr).interrupt_asap() };
+            },
+            // SAFETY: the solver outlives the call and is not accessed in
+            // any other way than the interrupt above
+            || unsafe { (*ptr).solve_with_assumptions(temp) },
+        );
+        match result {
+            cryptominisat::Lbool::True => SolveResult::Sat,
+            cryptominisat::Lbool::False => SolveResult::Unsat,
+            cryptominisat::Lbool::Undef => SolveResult::Unknown,
+        }
     }
 
     fn get_value(&self, lit: Literal) -> bool {
         self.solver.is_true(CryptoMiniSat::decode(lit))
     }
 
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        // the conflict clause contains the negated assumptions
+        self.solver
+            .get_conflict()
+            .iter()
+            .map(|lit| CryptoMiniSat::encode(!*lit))
+            .collect()
+    }
+
     fn get_name(&self) -> &'static str {
         "CryptoMiniSat"
     }
@@ -405,12 +866,61 @@ impl SatSolver for CryptoMiniSat {
     fn num_clauses(&self) -> usize {
         self.num_clauses
     }
+
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        Some(self.interrupt.clone())
+    }
+}
+
+/// Callbacks for BatSat to enforce the resource limits and interrupts, and
+/// to log the learnt and deleted clauses.
+#[cfg(feature = "batsat")]
+#[derive(Default)]
+struct BatSatCallbacks {
+    deadline: Option<Instant>,
+    conflicts: Option<u64>,
+    interrupt: Interrupt,
+    proof: Option<Vec<ProofStep<Literal>>>,
+    learnt: u64,
+    publish: bool,
+}
+
+#[cfg(feature = "batsat")]
+impl batsat::Callbacks for BatSatCallbacks {
+    fn on_new_clause(&mut self, clause: &[batsat::Lit], kind: batsat::ClauseKind) {
+        if kind == batsat::ClauseKind::Learnt {
+            self.learnt += 1;
+            if self.publish && self.learnt.is_multiple_of(LEARNT_PERIOD) {
+                set_progress(LEARNT_PROGRESS, self.learnt);
+            }
+            if let Some(conflicts) = self.conflicts.as_mut() {
+                *conflicts = conflicts.saturating_sub(1);
+            }
+            if let Some(proof) = self.proof.as_mut() {
+                let clause = clause.iter().map(|lit| BatSat::encode(*lit)).collect();
+                proof.push(ProofStep::Add(clause));
+            }
+        }
+    }
+
+    fn on_delete_clause(&mut self, clause: &[batsat::Lit]) {
+        if let Some(proof) = self.proof.as_mut() {
+            let clause = clause.iter().map(|lit| BatSat::encode(*lit)).collect();
+            proof.push(ProofStep::Delete(clause));
+        }
+    }
+
+    fn stop(&self) -> bool {
+        self.conflicts == Some(0)
+            || self.interrupt.is_interrupted()
+            || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
 }
 
 /// MiniSAT reimplemented in pure rust.
 #[cfg(feature = "batsat")]
 pub struct BatSat {
-    solver: batsat::BasicSolver,
+    solver: batsat::Solver<BatSatCallbacks>,
     temp: Vec<batsat::Lit>,
 }
 
@@ -457,17 +967,40 @@ impl SatSolver for BatSat {
         self.solver.add_clause_reuse(&mut self.temp);
     }
 
-    fn solve_with(&mut self, lits: &[Literal]) -> bool {
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+        let callbacks = self.solver.cb_mut();
+        if callbacks.interrupt.is_interrupted() {
+            return SolveResult::Unknown;
+        }
+        callbacks.deadline = limits.deadline();
+        callbacks.conflicts = limits.conflicts;
+
         self.temp.clear();
         self.temp
             .extend(lits.iter().map(|lit| BatSat::decode(*lit)));
-        self.solver.solve_limited(&self.temp) == batsat::lbool::TRUE
+        let result = self.solver.solve_limited(&self.temp);
+        if result == batsat::lbool::TRUE {
+            SolveResult::Sat
+        } else if result == batsat::lbool::FALSE {
+            SolveResult::Unsat
+        } else {
+            SolveResult::Unknown
+        }
     }
 
     fn get_value(&self, lit: Literal) -> bool {
         self.solver.value_lit(BatSat::decode(lit)) == batsat::lbool::TRUE
     }
 
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        // the conflict clause contains the negated assumptions
+        self.solver
+            .unsat_core()
+            .iter()
+            .map(|lit| BatSat::encode(!*lit))
+            .collect()
+    }
+
     fn get_name(&self) -> &'static str {
         "BatSat"
     }
@@ -479,22 +1012,112 @@ impl SatSolver for BatSat {
     fn num_clauses(&self) -> usize {
         self.solver.num_clauses() as usize
     }
+
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        Some(self.solver.cb().interrupt.clone())
+    }
+
+    fn enable_proof(&mut self) -> bool {
+        self.solver.cb_mut().proof = Some(Vec::new());
+        true
+    }
+
+    fn proof_steps(&mut self) -> Option<Vec<ProofStep<Literal>>> {
+        self.solver.cb().proof.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics {
+            conflicts: Some(self.solver.num_conflicts()),
+            decisions: Some(self.solver.num_decisions()),
+            propagations: Some(self.solver.num_propagations()),
+            restarts: Some(self.solver.num_restarts()),
+            learnt_clauses: Some(self.solver.cb().learnt),
+            ..Default::default()
+        }
+    }
+
+    fn publish_progress(&mut self, enable: bool) -> bool {
+        self.solver.cb_mut().publish = enable;
+        true
+    }
 }
 
-/// A state of the art SAT solver.
+/// Callbacks for CaDiCaL to enforce the time limit and interrupts, and to
+/// count the learnt clauses if the statistics are collected or published.
 #[cfg(feature = "cadical")]
 #[derive(Default)]
+struct CaDiCaLCallbacks {
+    deadline: Option<Instant>,
+    interrupt: Interrupt,
+    learnt: Arc<AtomicU64>,
+    collect: bool,
+    publish: bool,
+}
+
+#[cfg(feature = "cadical")]
+impl cadical::Callbacks for CaDiCaLCallbacks {
+    fn terminate(&mut self) -> bool {
+        self.interrupt.is_interrupted() || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    fn max_length(&self) -> i32 {
+        // every learnt clause is copied, so they are passed only if needed
+        if self.collect || self.publish {
+            i32::MAX
+        } else {
+            0
+        }
+    }
+
+    fn learn(&mut self, _clause: &[i32]) {
+        let learnt = self.learnt.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.publish && learnt.is_multiple_of(LEARNT_PERIOD) {
+            set_progress(LEARNT_PROGRESS, learnt);
+        }
+    }
+}
+
+/// A state of the art SAT solver. Proof logging is not supported, because
+/// the learn callback misses the clauses strengthened during conflict
+/// analysis, and the binding cannot turn that off. For the same reason the
+/// learnt clauses are only counted, the binding does not expose the other
+/// statistics. They are counted only while the statistics are collected.
+#[cfg(feature = "cadical")]
 pub struct CaDiCaL {
-    solver: cadical::Solver,
+    solver: cadical::Solver<CaDiCaLCallbacks>,
     num_vars: u32,
+    interrupt: Interrupt,
+    learnt: Arc<AtomicU64>,
+    collect: bool,
+    assumptions: Vec<Literal>,
 }
 
+#[cfg(feature = "cadical")]
+impl Default for CaDiCaL {
+    fn default() -> Self {
+        CaDiCaL::with_config("default")
+    }
+}
+
+#[cfg(feature = "cadical")]
 impl CaDiCaL {
     pub fn with_config(config: &str) -> Self {
-        let solver = cadical::Solver::with_config(config).unwrap();
+        let interrupt: Interrupt = Default::default();
+        let learnt: Arc<AtomicU64> = Default::default();
+        let mut solver = cadical::Solver::with_config(config).unwrap();
+        solver.set_callbacks(Some(CaDiCaLCallbacks {
+            interrupt: interrupt.clone(),
+            learnt: learnt.clone(),
+            ..Default::default()
+        }));
         CaDiCaL {
             solver,
             num_vars: 0,
+            interrupt,
+            learnt,
+            collect: false,
+            assumptions: Vec::new(),
         }
     }
 }
@@ -519,16 +1142,43 @@ impl SatSolver for CaDiCaL {
             .add_clause(lits.iter().map(|lit| lit.value as i32));
     }
 
-    fn solve_with(&mut self, lits: &[Literal]) -> bool {
-        self.solver
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+        if self.interrupt.is_interrupted() {
+            return SolveResult::Unknown;
+        }
+        if let Some(conflicts) = limits.conflicts {
+            let conflicts = conflicts.min(i32::MAX as u64) as i32;
+            self.solver.set_limit("conflicts", conflicts).unwrap();
+        }
+        self.solver.get_callbacks().unwrap().deadline = limits.deadline();
+        self.assumptions.clear();
+        self.assumptions.extend_from_slice(lits);
+
+        match self
+            .solver
             .solve_with(lits.iter().map(|lit| lit.value as i32))
-            .unwrap()
+        {
+            Some(true) => SolveResult::Sat,
+            Some(false) => SolveResult::Unsat,
+            None => SolveResult::Unknown,
+        }
     }
 
     fn get_value(&self, lit: Literal) -> bool {
         self.solver.value(lit.value as i32) == Some(true)
     }
 
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        if self.solver.status() != Some(false) {
+            return Vec::new();
+        }
+        self.assumptions
+            .iter()
+            .copied()
+            .filter(|lit| self.solver.failed(lit.value as i32))
+            .collect()
+    }
+
     fn get_name(&self) -> &'static str {
         "CaDiCaL"
     }
@@ -540,6 +1190,687 @@ impl SatSolver for CaDiCaL {
     fn num_clauses(&self) -> usize {
         self.solver.num_clauses() as usize
     }
+
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        Some(self.interrupt.clone())
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics {
+            learnt_clauses: self.collect.then(|| self.learnt.load(Ordering::Relaxed)),
+            ..Default::default()
+        }
+    }
+
+    fn collect_statistics(&mut self, enable: bool) -> bool {
+        self.collect = enable;
+        self.solver.get_callbacks().unwrap().collect = enable;
+        true
+    }
+
+    fn publish_progress(&mut self, enable: bool) -> bool {
+        self.solver.get_callbacks().unwrap().publish = enable;
+        true
+    }
+}
+
+/// A wrapper around a SAT solver that records every variable and clause
+/// added to it, so the problem can be exported in the DIMACS format.
+#[derive(Debug)]
+pub struct Recorder {
+    solver: Box<dyn SatSolver>,
+    formula: Formula,
+    literals: Vec<Literal>,
+    assumptions: Vec<Literal>,
+    temp: Vec<Literal>,
+}
+
+impl Recorder {
+    /// Creates a new recorder wrapping the given solver, which should not
+    /// have any variables yet.
+    pub fn new(solver: Box<dyn SatSolver>) -> Self {
+        Recorder {
+            solver,
+            formula: Default::default(),
+            literals: Vec::new(),
+            assumptions: Vec::new(),
+            temp: Vec::new(),
+        }
+    }
+
+    /// Returns the literal of the wrapped solver.
+    fn decode(&self, lit: Literal) -> Literal {
+        let lit = Formula::encode(lit);
+        let inner = self.literals[(lit.abs() - 1) as usize];
+        if lit < 0 {
+            self.solver.negate(inner)
+        } else {
+            inner
+        }
+    }
+
+    fn decode_temp(&mut self, lits: &[Literal]) {
+        self.temp.clear();
+        for &lit in lits {
+            let lit = self.decode(lit);
+            self.temp.push(lit);
+        }
+    }
+}
+
+impl SatSolver for Recorder {
+    fn add_variable(&mut self) -> Literal {
+        self.literals.push(self.solver.add_variable());
+        self.formula.add_variable()
+    }
+
+    fn negate(&self, lit: Literal) -> Literal {
+        Formula::negate(lit)
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        self.formula.add_clause(lits);
+        self.decode_temp(lits);
+        self.solver.add_clause(&self.temp);
+    }
+
+    fn add_xor_clause(&mut self, lit1: Literal, lit2: Literal, lit3: Literal) {
+        self.formula.add_xor_clause(&[lit1, lit2, lit3]);
+        let lit1 = self.decode(lit1);
+        let lit2 = self.decode(lit2);
+        let lit3 = self.decode(lit3);
+        self.solver.add_xor_clause(lit1, lit2, lit3);
+    }
+
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+        self.assumptions.clear();
+        self.assumptions.extend_from_slice(lits);
+        self.decode_temp(lits);
+        self.solver.solve_limited(&self.temp, limits)
+    }
+
+    fn get_value(&self, lit: Literal) -> bool {
+        self.solver.get_value(self.decode(lit))
+    }
+
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        let failed: HashSet<Literal> = self.solver.failed_assumptions().into_iter().collect();
+        self.assumptions
+            .iter()
+            .copied()
+            .filter(|lit| failed.contains(&self.decode(*lit)))
+            .collect()
+    }
+
+    fn get_name(&self) -> &'static str {
+        "Recorder"
+    }
+
+    fn num_variables(&self) -> u32 {
+        self.formula.num_variables()
+    }
+
+    fn num_clauses(&self) -> usize {
+        self.formula.num_clauses()
+    }
+
+    fn get_formula(&self) -> Option<&Formula> {
+        Some(&self.formula)
+    }
+
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        self.solver.interrupt_handle()
+    }
+
+    fn enable_proof(&mut self) -> bool {
+        self.solver.enable_proof()
+    }
+
+    fn proof_steps(&mut self) -> Option<Vec<ProofStep<Literal>>> {
+        let mut map: HashMap<Literal, Literal> = HashMap::new();
+        for (var, &inner) in self.literals.iter().enumerate() {
+            let lit = Formula::decode(var as i32 + 1);
+            map.insert(inner, lit);
+            map.insert(self.solver.negate(inner), Formula::negate(lit));
+        }
+        self.solver.proof_steps().map(|steps| {
+            steps
+                .into_iter()
+                .map(|step| step.map(|lit| map[&lit]))
+                .collect()
+        })
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.solver.statistics()
+    }
+
+    fn collect_statistics(&mut self, enable: bool) -> bool {
+        self.solver.collect_statistics(enable)
+    }
+
+    fn publish_progress(&mut self, enable: bool) -> bool {
+        self.solver.publish_progress(enable)
+    }
+}
+
+/// A command sent from a `Portfolio` to its worker threads.
+enum PortfolioCommand {
+    AddVariable,
+    AddClause(Vec<Literal>),
+    AddXorClause(Literal, Literal, Literal),
+    Solve(usize, Vec<Literal>, Limits),
+}
+
+/// The answer of a worker thread to a solve command. The model contains
+/// the value of every variable if the problem was satisfiable, and the
+/// failed assumptions are given if it was unsatisfiable.
+struct PortfolioAnswer {
+    worker: usize,
+    call: usize,
+    result: SolveResult,
+    model: Vec<bool>,
+    failed: Vec<Literal>,
+}
+
+/// The state of a worker thread kept by the `Portfolio`.
+struct PortfolioWorker {
+    name: &'static str,
+    commands: Sender<PortfolioCommand>,
+    interrupt: Option<Interrupt>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Runs several SAT solvers in parallel threads on the same problem. Every
+/// variable and clause is replayed into each solver, and each call returns
+/// the answer of the first solver that decides the problem, while the other
+/// ones are interrupted. The model and the failed assumptions are reported
+/// from the winning solver. The literals follow the DIMACS convention as in
+/// `Formula`.
+pub struct Portfolio {
+    workers: Vec<PortfolioWorker>,
+    answers: Receiver<PortfolioAnswer>,
+    cancelled: Arc<AtomicUsize>,
+    call: usize,
+    num_variables: u32,
+    num_clauses: usize,
+    model: Vec<bool>,
+    failed: Vec<Literal>,
+    winner: Option<usize>,
+    interrupt: Interrupt,
+}
+
+impl Portfolio {
+    /// Creates a new portfolio running the named solvers, see
+    /// `create_solver` for the valid names. Each solver is created within
+    /// its own thread.
+    pub fn new(names: &[&str]) -> Self {
+        assert!(!names.is_empty());
+        let cancelled: Arc<AtomicUsize> = Default::default();
+        let (answer_sender, answers) = channel();
+        let (handle_sender, handles) = channel();
+
+        let mut workers = Vec::with_capacity(names.len());
+        for (worker, name) in names.iter().enumerate() {
+            let (commands, receiver) = channel();
+            let name = name.to_string();
+            let answers = answer_sender.clone();
+            let handles = handle_sender.clone();
+            let cancelled = cancelled.clone();
+            let thread = spawn(move || {
+                let solver = create_solver(&name);
+                handles
+                    .send((worker, solver.get_name(), solver.interrupt_handle()))
+                    .unwrap();
+                drop(handles);
+                Portfolio::worker(solver, worker, receiver, answers, cancelled);
+            });
+            workers.push(PortfolioWorker {
+                name: "",
+                commands,
+                interrupt: None,
+                thread: Some(thread),
+            });
+        }
+        drop(handle_sender);
+
+        for _ in 0..names.len() {
+            let (worker, name, interrupt) = handles.recv().expect("portfolio worker failed");
+            workers[worker].name = name;
+            workers[worker].interrupt = interrupt;
+        }
+
+        Portfolio {
+            workers,
+            answers,
+            cancelled,
+            call: 0,
+            num_variables: 0,
+            num_clauses: 0,
+            model: Vec::new(),
+            failed: Vec::new(),
+            winner: None,
+            interrupt: Default::default(),
+        }
+    }
+
+    /// Returns the name of the solver that answered the last call, or
+    /// `None` if no solver could decide the problem.
+    pub fn get_winner(&self) -> Option<&'static str> {
+        self.winner.map(|worker| self.workers[worker].name)
+    }
+
+    /// The main loop of a worker thread, which stops when the portfolio is
+    /// dropped.
+    fn worker(
+        mut solver: Box<dyn SatSolver>,
+        worker: usize,
+        commands: Receiver<PortfolioCommand>,
+        answers: Sender<PortfolioAnswer>,
+        cancelled: Arc<AtomicUsize>,
+    ) {
+        let interrupt = solver.interrupt_handle();
+        let mut literals: Vec<Literal> = Vec::new();
+        let decode = |solver: &dyn SatSolver, literals: &[Literal], lit: Literal| {
+            let lit = Formula::encode(lit);
+            let inner = literals[(lit.abs() - 1) as usize];
+            if lit < 0 {
+                solver.negate(inner)
+            } else {
+                inner
+            }
+        };
+
+        for command in commands {
+            match command {
+                PortfolioCommand::AddVariable => {
+                    literals.push(solver.add_variable());
+                }
+                PortfolioCommand::AddClause(lits) => {
+                    let lits: Vec<Literal> = lits
+                        .into_iter()
+                        .map(|lit| decode(solver.as_ref(), &literals, lit))
+                        .collect();
+                    solver.add_clause(&lits);
+                }
+                PortfolioCommand::AddXorClause(lit1, lit2, lit3) => {
+                    let lit1 = decode(solver.as_ref(), &literals, lit1);
+                    let lit2 = decode(solver.as_ref(), &literals, lit2);
+                    let lit3 = decode(solver.as_ref(), &literals, lit3);
+                    solver.add_xor_clause(lit1, lit2, lit3);
+                }
+                PortfolioCommand::Solve(call, lits, limits) => {
+                    // clear the interrupt before checking the cancellation,
+                    // so a cancellation in between still stops the search
+                    if let Some(interrupt) = interrupt.as_ref() {
+                        interrupt.clear();
+                    }
+                    let inner: Vec<Literal> = lits
+                        .iter()
+                        .map(|&lit| decode(solver.as_ref(), &literals, lit))
+                        .collect();
+                    let result = if cancelled.load(Ordering::SeqCst) >= call {
+                        SolveResult::Unknown
+                    } else {
+                        solver.solve_limited(&inner, &limits)
+                    };
+
+                    let mut model = Vec::new();
+                    let mut failed = Vec::new();
+                    if result == SolveResult::Sat {
+                        model = literals.iter().map(|&lit| solver.get_value(lit)).collect();
+                    } else if result == SolveResult::Unsat {
+                        let set: HashSet<Literal> =
+                            solver.failed_assumptions().into_iter().collect();
+                        failed = lits
+                            .iter()
+                            .zip(inner.iter())
+                            .filter(|(_, lit)| set.contains(lit))
+                            .map(|(&lit, _)| lit)
+                            .collect();
+                    }
+
+                    let answer = PortfolioAnswer {
+                        worker,
+                        call,
+                        result,
+                        model,
+                        failed,
+                    };
+                    if answers.send(answer).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends the command to all workers.
+    fn broadcast<F>(&self, command: F)
+    where
+        F: Fn() -> PortfolioCommand,
+    {
+        for worker in self.workers.iter() {
+            worker
+                .commands
+                .send(command())
+                .expect("portfolio worker failed");
+        }
+    }
+
+    /// Interrupts all workers still running the given or an earlier call.
+    fn cancel(&self, call: usize) {
+        self.cancelled.store(call, Ordering::SeqCst);
+        for worker in self.workers.iter() {
+            if let Some(interrupt) = worker.interrupt.as_ref() {
+                interrupt.interrupt();
+            }
+        }
+    }
+}
+
+impl SatSolver for Portfolio {
+    fn add_variable(&mut self) -> Literal {
+        self.num_variables += 1;
+        self.broadcast(|| PortfolioCommand::AddVariable);
+        Formula::decode(self.num_variables as i32)
+    }
+
+    fn negate(&self, lit: Literal) -> Literal {
+        Formula::negate(lit)
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        self.num_clauses += 1;
+        self.broadcast(|| PortfolioCommand::AddClause(lits.to_vec()));
+    }
+
+    fn add_xor_clause(&mut self, lit1: Literal, lit2: Literal, lit3: Literal) {
+        self.num_clauses += 1;
+        self.broadcast(|| PortfolioCommand::AddXorClause(lit1, lit2, lit3));
+    }
+
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+        self.winner = None;
+        self.model.clear();
+        self.failed.clear();
+        if self.interrupt.is_interrupted() {
+            return SolveResult::Unknown;
+        }
+
+        self.call += 1;
+        let call = self.call;
+        self.broadcast(|| PortfolioCommand::Solve(call, lits.to_vec(), *limits));
+
+        // answers of earlier calls are ignored
+        let mut pending = self.workers.len();
+        let result = loop {
+            match self.answers.recv_timeout(Duration::from_millis(10)) {
+                Ok(answer) if answer.call == call => {
+                    pending -= 1;
+                    if answer.result != SolveResult::Unknown {
+                        self.winner = Some(answer.worker);
+                        self.model = answer.model;
+                        self.failed = answer.failed;
+                        break answer.result;
+                    } else if pending == 0 {
+                        break SolveResult::Unknown;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.interrupt.is_interrupted() {
+                        break SolveResult::Unknown;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => panic!("portfolio workers failed"),
+            }
+        };
+
+        self.cancel(self.call);
+        result
+    }
+
+    fn get_value(&self, lit: Literal) -> bool {
+        let lit = Formula::encode(lit);
+        self.model[(lit.abs() - 1) as usize] == (lit > 0)
+    }
+
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        self.failed.clone()
+    }
+
+    fn get_name(&self) -> &'static str {
+        "Portfolio"
+    }
+
+    fn num_variables(&self) -> u32 {
+        self.num_variables
+    }
+
+    fn num_clauses(&self) -> usize {
+        self.num_clauses
+    }
+
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        Some(self.interrupt.clone())
+    }
+}
+
+impl Drop for Portfolio {
+    fn drop(&mut self) {
+        self.cancel(usize::MAX);
+        for mut worker in self.workers.drain(..) {
+            drop(worker.commands);
+            if let Some(thread) = worker.thread.take() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+/// Splits the command line into words separated by whitespace. Single and
+/// double quotes group words with whitespace, and a backslash outside of
+/// single quotes escapes the next character.
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.next());
+            }
+            (None, '\'') | (None, '"') => {
+                word.get_or_insert_with(String::new);
+                quote = Some(c);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    assert!(quote.is_none(), "unterminated quote in {}", command);
+    words.extend(word);
+    words
+}
+
+/// A SAT solver running in an external process for each call of
+/// `solve_with`. The problem is written in DIMACS format (with the
+/// assumptions as unit clauses) to the standard input of the process, and
+/// the result is parsed from its standard output in the SAT competition
+/// format. The result is unknown if the output has no status line.
+#[derive(Debug)]
+pub struct External {
+    program: String,
+    args: Vec<String>,
+    formula: Formula,
+    solution: bit_vec::BitVec,
+    interrupt: Interrupt,
+    assumptions: Vec<Literal>,
+}
+
+impl External {
+    /// Creates a new external solver running the given program with the
+    /// given arguments.
+    pub fn new<ARGS>(program: String, args: ARGS) -> Self
+    where
+        ARGS: IntoIterator<Item = String>,
+    {
+        External {
+            program,
+            args: args.into_iter().collect(),
+            formula: Default::default(),
+            solution: bit_vec::BitVec::new(),
+            interrupt: Default::default(),
+            assumptions: Vec::new(),
+        }
+    }
+
+    /// Runs the external program and returns its standard output, or
+    /// `None` if the program was killed because of the time limit or an
+    /// interrupt.
+    fn run(&self, input: Vec<u8>, limits: &Limits) -> Option<Vec<u8>> {
+        let deadline = limits.deadline();
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|err| panic!("cannot start {}: {}", self.program, err));
+
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = spawn(move || {
+            // the solver may exit without reading the whole problem
+            let _ = stdin.write_all(&input);
+        });
+
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+
+        let mut finished = true;
+        while child.try_wait().unwrap().is_none() {
+            if self.interrupt.is_interrupted() || deadline.is_some_and(|d| Instant::now() >= d) {
+                let _ = child.kill();
+                child.wait().unwrap();
+                finished = false;
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+
+        writer.join().unwrap();
+        let output = reader.join().unwrap().unwrap();
+        if finished {
+            Some(output)
+        } else {
+            None
+        }
+    }
+}
+
+impl SatSolver for External {
+    fn add_variable(&mut self) -> Literal {
+        self.formula.add_variable()
+    }
+
+    fn negate(&self, lit: Literal) -> Literal {
+        Formula::negate(lit)
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        self.formula.add_clause(lits);
+    }
+
+    fn add_xor_clause(&mut self, lit1: Literal, lit2: Literal, lit3: Literal) {
+        self.formula.add_xor_clause(&[lit1, lit2, lit3]);
+    }
+
+    fn solve_limited(&mut self, lits: &[Literal], limits: &Limits) -> SolveResult {
+        if self.interrupt.is_interrupted() {
+            return SolveResult::Unknown;
+        }
+        self.assumptions.clear();
+        self.assumptions.extend_from_slice(lits);
+
+        let mut input: Vec<u8> = Vec::new();
+        let options = DimacsOptions {
+            xor_lines: false,
+            assumption_units: true,
+        };
+        self.formula
+            .write_dimacs(&mut input, lits, options)
+            .unwrap();
+        let output = match self.run(input, limits) {
+            Some(output) => output,
+            None => return SolveResult::Unknown,
+        };
+
+        let mut status = None;
+        self.solution.truncate(0);
+        self.solution
+            .grow(self.formula.num_variables() as usize, false);
+        for line in output.lines() {
+            let line = line.unwrap();
+            if let Some(rest) = line.strip_prefix("s ") {
+                status = Some(match rest.trim() {
+                    "SATISFIABLE" => SolveResult::Sat,
+                    "UNSATISFIABLE" => SolveResult::Unsat,
+                    _ => SolveResult::Unknown,
+                });
+            } else if let Some(rest) = line.strip_prefix("v ") {
+                for token in rest.split_whitespace() {
+                    let lit: i32 = token
+                        .parse()
+                        .unwrap_or_else(|_| panic!("invalid solver output: {}", line));
+                    let var = lit.unsigned_abs() as usize;
+                    if lit > 0 && var <= self.solution.len() {
+                        self.solution.set(var - 1, true);
+                    }
+                }
+            }
+        }
+
+        // the program may have crashed without printing the status line
+        status.unwrap_or(SolveResult::Unknown)
+    }
+
+    fn get_value(&self, lit: Literal) -> bool {
+        let lit = Formula::encode(lit);
+        let var = lit.unsigned_abs() as usize - 1;
+        self.solution.get(var).unwrap() ^ (lit < 0)
+    }
+
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        // the competition output format does not report the failed ones
+        self.assumptions.clone()
+    }
+
+    fn get_name(&self) -> &'static str {
+        "External"
+    }
+
+    fn num_variables(&self) -> u32 {
+        self.formula.num_variables()
+    }
+
+    fn num_clauses(&self) -> usize {
+        self.formula.num_clauses()
+    }
+
+    fn get_formula(&self) -> Option<&Formula> {
+        Some(&self.formula)
+    }
+
+    fn interrupt_handle(&self) -> Option<Interrupt> {
+        Some(self.interrupt.clone())
+    }
 }
 
 #[cfg(test)]
@@ -550,7 +1881,7 @@ mod tests {
         let a = sat.add_variable();
         let b = sat.add_variable();
         sat.add_clause(&[a, b]);
-        assert!(sat.solve_with(&[sat.negate(b)]));
+        assert_eq!(sat.solve_with(&[sat.negate(b)]), SolveResult::Sat);
         assert!(sat.get_value(a));
         assert!(!sat.get_value(b));
         sat.add_clause(&[sat.negate(a), b]);
@@ -559,12 +1890,87 @@ mod tests {
         assert_eq!(sat.num_clauses(), 3);
         let c = sat.add_variable();
         sat.add_xor_clause(a, b, c);
-        assert!(sat.solve());
+        assert_eq!(sat.solve(), SolveResult::Sat);
         assert!(!sat.get_value(a));
         assert!(sat.get_value(b));
         assert!(sat.get_value(c));
         sat.add_clause(&[a, sat.negate(b)]);
-        assert!(!sat.solve());
+        assert_eq!(sat.solve(), SolveResult::Unsat);
+    }
+
+    /// Adds the unsatisfiable pigeonhole problem and returns the variables
+    /// for each pigeon.
+    #[cfg(any(feature = "cadical", feature = "batsat"))]
+    fn add_pigeonhole(sat: &mut dyn SatSolver, holes: usize) -> Vec<Vec<Literal>> {
+        let vars: Vec<Vec<Literal>> = (0..=holes)
+            .map(|_| (0..holes).map(|_| sat.add_variable()).collect())
+            .collect();
+        for pigeon in vars.iter() {
+            sat.add_clause(pigeon);
+        }
+        for hole in 0..holes {
+            for i in 0..vars.len() {
+                for j in 0..i {
+                    let lit1 = sat.negate(vars[i][hole]);
+                    let lit2 = sat.negate(vars[j][hole]);
+                    sat.add_clause(&[lit1, lit2]);
+                }
+            }
+        }
+        vars
+    }
+
+    /// Checks the limits on the unsatisfiable pigeonhole problem.
+    #[cfg(any(feature = "cadical", feature = "batsat"))]
+    fn test_limits(sat: &mut dyn SatSolver) {
+        add_pigeonhole(sat, 7);
+        let limits = Limits {
+            conflicts: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(sat.solve_limited(&[], &limits), SolveResult::Unknown);
+        let limits = Limits {
+            time: Some(Duration::from_secs(0)),
+            ..Default::default()
+        };
+        assert_eq!(sat.solve_limited(&[], &limits), SolveResult::Unknown);
+        assert_eq!(sat.solve(), SolveResult::Unsat);
+    }
+
+    /// Interrupts the solver on a hard problem from another thread.
+    #[cfg(any(feature = "cadical", feature = "batsat"))]
+    fn test_interrupt(sat: &mut dyn SatSolver) {
+        let vars = add_pigeonhole(sat, 10);
+        let handle = sat.interrupt_handle().unwrap();
+        let thread = {
+            let handle = handle.clone();
+            spawn(move || {
+                sleep(Duration::from_millis(100));
+                handle.interrupt();
+            })
+        };
+        assert_eq!(sat.solve(), SolveResult::Unknown);
+        thread.join().unwrap();
+        assert_eq!(sat.solve(), SolveResult::Unknown);
+
+        handle.clear();
+        let lits: Vec<Literal> = vars[0].iter().map(|&lit| sat.negate(lit)).collect();
+        assert_eq!(sat.solve_with(&lits), SolveResult::Unsat);
+    }
+
+    /// Checks that only the responsible assumptions are reported.
+    fn test_failed(sat: &mut dyn SatSolver) {
+        let a = sat.add_variable();
+        let b = sat.add_variable();
+        let c = sat.add_variable();
+        sat.add_clause(&[sat.negate(a), b]);
+        let not_b = sat.negate(b);
+        assert_eq!(sat.solve_with(&[a, c, not_b]), SolveResult::Unsat);
+        let mut failed = sat.failed_assumptions();
+        failed.sort_by_key(|lit| lit.value);
+        let mut expected = vec![a, not_b];
+        expected.sort_by_key(|lit| lit.value);
+        assert_eq!(failed, expected);
     }
 
     #[cfg(feature = "minisat")]
@@ -593,6 +1999,14 @@ mod tests {
     fn batsat() {
         let mut sat: BatSat = Default::default();
         test(&mut sat);
+        let mut sat: BatSat = Default::default();
+        test_limits(&mut sat);
+        let mut sat: BatSat = Default::default();
+        test_interrupt(&mut sat);
+        let mut sat: BatSat = Default::default();
+        test_failed(&mut sat);
+        let mut sat: BatSat = Default::default();
+        test_statistics(&mut sat);
     }
 
     #[cfg(feature = "cadical")]
@@ -600,5 +2014,123 @@ mod tests {
     fn cadical() {
         let mut sat: CaDiCaL = Default::default();
         test(&mut sat);
+        let mut sat: CaDiCaL = Default::default();
+        test_limits(&mut sat);
+        let mut sat: CaDiCaL = Default::default();
+        test_interrupt(&mut sat);
+        let mut sat: CaDiCaL = Default::default();
+        test_failed(&mut sat);
+        let mut sat: CaDiCaL = Default::default();
+        test_statistics(&mut sat);
+
+        // only the empty clause is passed when the statistics are not needed
+        let mut sat: CaDiCaL = Default::default();
+        add_pigeonhole(&mut sat, 5);
+        assert_eq!(sat.solve(), SolveResult::Unsat);
+        assert_eq!(sat.statistics().learnt_clauses, None);
+        assert_eq!(sat.learnt.load(Ordering::Relaxed), 1);
+    }
+
+    /// Checks the counters on the unsatisfiable pigeonhole problem.
+    #[cfg(any(feature = "cadical", feature = "batsat"))]
+    fn test_statistics(sat: &mut dyn SatSolver) {
+        sat.collect_statistics(true);
+        assert_eq!(sat.statistics().learnt_clauses, Some(0));
+        add_pigeonhole(sat, 5);
+        assert!(sat.publish_progress(true));
+        assert_eq!(sat.solve(), SolveResult::Unsat);
+        let stats = sat.statistics();
+        assert!(stats.learnt_clauses.unwrap() > 0);
+        assert!(stats.conflicts.is_none() || stats.conflicts.unwrap() > 0);
+        assert_eq!(stats.since(&stats).learnt_clauses, Some(0));
+        assert_eq!(stats.calls, 0);
+    }
+
+    #[test]
+    fn portfolio() {
+        let mut sat = Portfolio::new(&["", ""]);
+        test(&mut sat);
+        #[cfg(any(feature = "batsat", feature = "cadical"))]
+        {
+            let mut sat = Portfolio::new(&["", ""]);
+            test_limits(&mut sat);
+            let mut sat = Portfolio::new(&["", ""]);
+            test_interrupt(&mut sat);
+        }
+        let mut sat = Portfolio::new(&["", ""]);
+        test_failed(&mut sat);
+        assert!(sat.get_winner().is_some());
+
+        let mut sat = create_solver("portfolio");
+        test(sat.as_mut());
+        assert_eq!(sat.get_name(), "Portfolio");
+    }
+
+    #[test]
+    #[should_panic(expected = "portfolio worker failed")]
+    fn portfolio_unknown() {
+        Portfolio::new(&["", "bogus"]);
+    }
+
+    #[test]
+    fn recorder() {
+        let mut sat = Recorder::new(create_solver(""));
+        test_failed(&mut sat);
+        let mut sat = Recorder::new(create_solver(""));
+        test(&mut sat);
+        let formula = sat.get_formula().unwrap();
+        assert_eq!(formula.num_variables(), 3);
+        assert_eq!(formula.clauses().len(), 4);
+        assert_eq!(formula.xor_clauses(), &[vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn command_line() {
+        assert_eq!(split_command("  a  b\tc "), vec!["a", "b", "c"]);
+        assert_eq!(
+            split_command(r#"'/my solver' "x y" a\ b '' z\'s"#),
+            vec!["/my solver", "x y", "a b", "", "z's"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        // a fake solver that knows the only model of the test problem
+        let script = "#!/bin/sh\n\
+            if grep -q '^-2 0$'; then\n\
+            echo 's UNSATISFIABLE'\n\
+            exit 20\n\
+            fi\n\
+            echo 'c fake solver'\n\
+            echo 's SATISFIABLE'\n\
+            echo 'v -1 2'\n\
+            echo 'v 3 0'\n\
+            exit 10\n";
+        let path = std::env::temp_dir().join(format!("uasat fake {}.sh", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut sat = create_solver(&format!("external:'{}' --unused", path.display()));
+        let a = sat.add_variable();
+        let b = sat.add_variable();
+        let c = sat.add_variable();
+        sat.add_clause(&[a, b]);
+        sat.add_xor_clause(a, b, c);
+        assert_eq!(sat.solve(), SolveResult::Sat);
+        assert!(!sat.get_value(a));
+        assert!(sat.get_value(b));
+        assert!(!sat.get_value(sat.negate(c)));
+        assert_eq!(sat.solve_with(&[sat.negate(b)]), SolveResult::Unsat);
+        assert_eq!(sat.num_clauses(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+
+        let mut sat = External::new("false".into(), []);
+        let a = sat.add_variable();
+        sat.add_clause(&[a]);
+        assert_eq!(sat.solve(), SolveResult::Unknown);
     }
 }
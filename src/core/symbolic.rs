@@ -0,0 +1,396 @@
+/*
+* Copyright (C) 2021, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Symbolic boolean expressions for inspecting the built formulas.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+
+use super::{BoolAlg, Shape, Tensor};
+
+/// A handle of a shared expression tree in a `Symbolic` algebra.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SymExpr {
+    index: u32,
+}
+
+/// The constant false expression.
+const FALSE: SymExpr = SymExpr { index: 0 };
+
+/// The constant true expression.
+const TRUE: SymExpr = SymExpr { index: 1 };
+
+/// A node of an expression tree.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum SymNode {
+    Const(bool),
+    Var(String),
+    Not(SymExpr),
+    And(SymExpr, SymExpr),
+    Or(SymExpr, SymExpr),
+    Xor(SymExpr, SymExpr),
+}
+
+/// A boolean algebra whose elements are symbolic expressions over named
+/// variables. Equal subexpressions are shared, and the shared ones are
+/// printed as let-bindings named `t` followed by a number, so the variable
+/// names should not have this form. The constants are folded and double
+/// negations are removed, but no other simplifications are made, so the
+/// printed formulas follow the construction closely.
+#[derive(Debug)]
+pub struct Symbolic {
+    nodes: Vec<SymNode>,
+    hash: HashMap<SymNode, SymExpr>,
+    limit: usize,
+}
+
+impl Default for Symbolic {
+    fn default() -> Self {
+        let mut alg = Symbolic {
+            nodes: Vec::new(),
+            hash: HashMap::new(),
+            limit: 1000,
+        };
+        alg.make(SymNode::Const(false));
+        alg.make(SymNode::Const(true));
+        alg
+    }
+}
+
+impl Symbolic {
+    /// Creates a new algebra without variables.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the largest number of distinct subexpressions a printed formula
+    /// can have, larger formulas are replaced with a short summary.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Returns the variable with the given name, which is created if it
+    /// does not exist yet.
+    pub fn add_variable(&mut self, name: &str) -> SymExpr {
+        self.make(SymNode::Var(name.to_string()))
+    }
+
+    /// Returns a tensor of variables of the given shape, whose names are
+    /// the given name followed by the coordinates separated by underscores.
+    pub fn tensor_add_variable(&mut self, name: &str, shape: Shape) -> Tensor<SymExpr> {
+        Tensor::create(shape, |coords| {
+            let mut var = name.to_string();
+            for coord in coords {
+                write!(var, "_{}", coord).unwrap();
+            }
+            self.add_variable(&var)
+        })
+    }
+
+    /// Returns the number of distinct expressions built so far.
+    pub fn num_exprs(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the human readable form of the expression, where the shared
+    /// subexpressions are introduced by let-bindings on separate lines.
+    pub fn format(&self, expr: SymExpr) -> String {
+        let shared = match self.shared(&[expr]) {
+            Some(shared) => shared,
+            None => return self.summary(expr),
+        };
+        let mut result = String::new();
+        for &index in shared.iter() {
+            let value = self.render(self.nodes[index as usize].clone(), &shared, false);
+            writeln!(result, "let t{} = {};", index, value).unwrap();
+        }
+        result.push_str(&self.render_expr(expr, &shared, false));
+        result
+    }
+
+    /// Returns the human readable form of each entry of the tensor on a
+    /// separate line (or block) prefixed by its coordinates.
+    pub fn format_tensor(&self, tensor: &Tensor<SymExpr>) -> String {
+        let shape = tensor.shape();
+        let mut result = String::new();
+        let mut coords = vec![0; shape.len()];
+        for _ in 0..shape.size() {
+            let expr = self.format(tensor.very_slow_get(&coords));
+            writeln!(result, "{:?}: {}", coords, expr).unwrap();
+            for (coord, &dim) in coords.iter_mut().zip(shape.dims().iter()) {
+                *coord += 1;
+                if *coord >= dim {
+                    *coord = 0;
+                } else {
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Writes the assertion of the given expressions in the SMT-LIB 2
+    /// format, where the shared subexpressions are defined as functions.
+    /// The size limit does not apply here.
+    pub fn write_smtlib(&self, output: &mut dyn io::Write, asserts: &[SymExpr]) -> io::Result<()> {
+        let mut shared = Vec::new();
+        let mut vars = Vec::new();
+        let counts = self.references(asserts);
+        for (index, &count) in counts.iter().enumerate() {
+            match self.nodes[index] {
+                SymNode::Var(ref name) if count > 0 => vars.push(name),
+                SymNode::Not(_) | SymNode::And(..) | SymNode::Or(..) | SymNode::Xor(..)
+                    if count > 1 =>
+                {
+                    shared.push(index as u32)
+                }
+                _ => {}
+            }
+        }
+
+        writeln!(output, "(set-logic QF_UF)")?;
+        for var in vars {
+            writeln!(output, "(declare-const {} Bool)", var)?;
+        }
+        for &index in shared.iter() {
+            let value = self.render(self.nodes[index as usize].clone(), &shared, true);
+            writeln!(output, "(define-fun t{} () Bool {})", index, value)?;
+        }
+        for &expr in asserts {
+            writeln!(output, "(assert {})", self.render_expr(expr, &shared, true))?;
+        }
+        writeln!(output, "(check-sat)")
+    }
+
+    /// Returns the handle of the given node, creating it if needed.
+    fn make(&mut self, node: SymNode) -> SymExpr {
+        if let Some(&expr) = self.hash.get(&node) {
+            return expr;
+        }
+        let expr = SymExpr {
+            index: self.nodes.len() as u32,
+        };
+        self.nodes.push(node.clone());
+        self.hash.insert(node, expr);
+        expr
+    }
+
+    /// Returns the number of times each expression is referenced from the
+    /// given roots (counting the roots once) and other referenced nodes.
+    fn references(&self, roots: &[SymExpr]) -> Vec<usize> {
+        let last = roots.iter().map(|expr| expr.index).max().unwrap_or(0);
+        let mut counts = vec![0; last as usize + 1];
+        for root in roots {
+            counts[root.index as usize] += 1;
+        }
+        for index in (0..counts.len()).rev() {
+            if counts[index] == 0 {
+                continue;
+            }
+            match self.nodes[index] {
+                SymNode::Not(expr) => counts[expr.index as usize] += 1,
+                SymNode::And(expr1, expr2)
+                | SymNode::Or(expr1, expr2)
+                | SymNode::Xor(expr1, expr2) => {
+                    counts[expr1.index as usize] += 1;
+                    counts[expr2.index as usize] += 1;
+                }
+                _ => {}
+            }
+        }
+        counts
+    }
+
+    /// Returns the shared compound subexpressions of the given roots in
+    /// topological order, or `None` if there are too many subexpressions.
+    fn shared(&self, roots: &[SymExpr]) -> Option<Vec<u32>> {
+        let counts = self.references(roots);
+        if counts.iter().filter(|&&count| count > 0).count() > self.limit {
+            return None;
+        }
+        let shared = counts
+            .iter()
+            .enumerate()
+            .filter(|&(index, &count)| {
+                count > 1 && !matches!(self.nodes[index], SymNode::Const(_) | SymNode::Var(_))
+            })
+            .map(|(index, _)| index as u32)
+            .collect();
+        Some(shared)
+    }
+
+    /// Returns a short description of an expression that is too large.
+    fn summary(&self, expr: SymExpr) -> String {
+        let count = self
+            .references(&[expr])
+            .iter()
+            .filter(|&&count| count > 0)
+            .count();
+        format!("<expression with {} subexpressions>", count)
+    }
+
+    /// Returns the expression as a reference to its binding if it is shared.
+    fn render_expr(&self, expr: SymExpr, shared: &[u32], smt: bool) -> String {
+        if shared.binary_search(&expr.index).is_ok() {
+            format!("t{}", expr.index)
+        } else {
+            self.render(self.nodes[expr.index as usize].clone(), shared, smt)
+        }
+    }
+
+    /// Returns the node in infix or SMT-LIB notation.
+    fn render(&self, node: SymNode, shared: &[u32], smt: bool) -> String {
+        let binary = |op: &str, smt_op: &str, expr1: SymExpr, expr2: SymExpr| {
+            let expr1 = self.render_expr(expr1, shared, smt);
+            let expr2 = self.render_expr(expr2, shared, smt);
+            if smt {
+                format!("({} {} {})", smt_op, expr1, expr2)
+            } else {
+                format!("({} {} {})", expr1, op, expr2)
+            }
+        };
+        match node {
+            SymNode::Const(value) => value.to_string(),
+            SymNode::Var(name) => name,
+            SymNode::Not(expr) => {
+                let expr = self.render_expr(expr, shared, smt);
+                if smt {
+                    format!("(not {})", expr)
+                } else {
+                    format!("!{}", expr)
+                }
+            }
+            SymNode::And(expr1, expr2) => binary("&", "and", expr1, expr2),
+            SymNode::Or(expr1, expr2) => binary("|", "or", expr1, expr2),
+            SymNode::Xor(expr1, expr2) => binary("^", "xor", expr1, expr2),
+        }
+    }
+}
+
+impl BoolAlg for Symbolic {
+    type Elem = SymExpr;
+
+    fn bool_lift(&self, elem: bool) -> Self::Elem {
+        if elem {
+            TRUE
+        } else {
+            FALSE
+        }
+    }
+
+    fn bool_not(&mut self, elem: Self::Elem) -> Self::Elem {
+        match self.nodes[elem.index as usize] {
+            SymNode::Const(value) => self.bool_lift(!value),
+            SymNode::Not(expr) => expr,
+            _ => self.make(SymNode::Not(elem)),
+        }
+    }
+
+    fn bool_or(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        if elem1 == TRUE || elem2 == TRUE {
+            TRUE
+        } else if elem1 == FALSE || elem1 == elem2 {
+            elem2
+        } else if elem2 == FALSE {
+            elem1
+        } else {
+            self.make(SymNode::Or(elem1, elem2))
+        }
+    }
+
+    fn bool_xor(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        if elem1 == FALSE {
+            elem2
+        } else if elem2 == FALSE {
+            elem1
+        } else if elem1 == TRUE {
+            self.bool_not(elem2)
+        } else if elem2 == TRUE {
+            self.bool_not(elem1)
+        } else if elem1 == elem2 {
+            FALSE
+        } else {
+            self.make(SymNode::Xor(elem1, elem2))
+        }
+    }
+
+    fn bool_and(&mut self, elem1: Self::Elem, elem2: Self::Elem) -> Self::Elem {
+        if elem1 == FALSE || elem2 == FALSE {
+            FALSE
+        } else if elem1 == TRUE || elem1 == elem2 {
+            elem2
+        } else if elem2 == TRUE {
+            elem1
+        } else {
+            self.make(SymNode::And(elem1, elem2))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TensorAlg;
+    use super::*;
+
+    #[test]
+    fn format() {
+        let mut alg = Symbolic::new();
+        let a = alg.add_variable("a");
+        let b = alg.add_variable("b");
+        let c = alg.add_variable("c");
+        let d = alg.bool_and(a, b);
+        let e = alg.bool_or(d, c);
+        let f = alg.bool_not(d);
+        let g = alg.bool_xor(e, f);
+        assert_eq!(alg.format(e), "((a & b) | c)");
+        assert_eq!(alg.format(g), "let t5 = (a & b);\n((t5 | c) ^ !t5)");
+        assert_eq!(alg.bool_not(f), d);
+        let h = alg.bool_imp(alg.bool_unit(), c);
+        assert_eq!(h, c);
+
+        alg.set_limit(4);
+        assert_eq!(alg.format(g), "<expression with 7 subexpressions>");
+
+        let mut output = Vec::new();
+        alg.write_smtlib(&mut output, &[g, c]).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(
+            text,
+            "(set-logic QF_UF)\n\
+            (declare-const a Bool)\n\
+            (declare-const b Bool)\n\
+            (declare-const c Bool)\n\
+            (define-fun t5 () Bool (and a b))\n\
+            (assert (xor (or t5 c) (not t5)))\n\
+            (assert c)\n\
+            (check-sat)\n"
+        );
+    }
+
+    #[test]
+    fn tensors() {
+        let mut alg = Symbolic::new();
+        let rel = alg.tensor_add_variable("r", Shape::new(vec![2, 2]));
+        let tmp = alg.tensor_and(rel.clone(), rel.polymer(Shape::new(vec![2, 2]), &[1, 0]));
+        let tmp = alg.tensor_any(tmp);
+        assert_eq!(
+            alg.format_tensor(&tmp),
+            "[0]: (r_0_0 | (r_1_0 & r_0_1))\n[1]: ((r_0_1 & r_1_0) | r_1_1)\n"
+        );
+    }
+}
@@ -19,7 +19,11 @@
 
 use std::ops;
 
-use super::{BoolAlg, BoolSat, GenElem, GenVec, GenVector as _};
+use super::{
+    approx, optimize, sampler, ApproxCount, BigNum, BoolAlg, BoolSat, CardEncoding, CubeConfig,
+    GenElem, GenVec, GenVector as _, Interrupted, LexOptimum, Limits, Models, Optimum, PbEncoding,
+    Strategy,
+};
 
 /// The shape of a tensor.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -340,6 +344,66 @@ pub trait TensorAlg {
     /// Returns a new tensor with the first dimension removed where the result
     /// is the at most one set predicate.
     fn tensor_amo(&mut self, elem: Self::Elem) -> Self::Elem;
+
+    /// Returns a new tensor with the first dimension removed where the result
+    /// is the at least `bound` many set predicate.
+    fn tensor_atleast(
+        &mut self,
+        elem: Self::Elem,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem;
+
+    /// Returns a new tensor with the first dimension removed where the result
+    /// is the at most `bound` many set predicate.
+    fn tensor_atmost(
+        &mut self,
+        elem: Self::Elem,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem;
+
+    /// Returns a new tensor with the first dimension removed where the result
+    /// is the exactly `bound` many set predicate.
+    fn tensor_exactly(
+        &mut self,
+        elem: Self::Elem,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem;
+
+    /// Returns a new tensor with the first dimension removed where the result
+    /// is the predicate that the sum of the weights of the true elements is
+    /// at most the given bound. The weights must have the same shape.
+    fn tensor_pb_atmost(
+        &mut self,
+        elem: Self::Elem,
+        weights: Tensor<i64>,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem;
+
+    /// Returns a new tensor with the first dimension removed where the result
+    /// is the predicate that the sum of the weights of the true elements is
+    /// at least the given bound. The weights must have the same shape.
+    fn tensor_pb_atleast(
+        &mut self,
+        elem: Self::Elem,
+        weights: Tensor<i64>,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem;
+
+    /// Returns a new tensor with the first dimension removed where the result
+    /// is the predicate that the sum of the weights of the true elements is
+    /// exactly the given bound. The weights must have the same shape.
+    fn tensor_pb_exactly(
+        &mut self,
+        elem: Self::Elem,
+        weights: Tensor<i64>,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem;
 }
 
 impl<ALG> TensorAlg for ALG
@@ -487,6 +551,111 @@ where
             .collect();
         Tensor::new(shape, elems)
     }
+
+    fn tensor_atleast(
+        &mut self,
+        elem: Self::Elem,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem {
+        let (head, shape) = elem.shape.split1();
+        let elems = elem
+            .elems
+            .split(head)
+            .iter()
+            .map(|v| self.bool_fold_atleast(v.iter(), bound, encoding))
+            .collect();
+        Tensor::new(shape, elems)
+    }
+
+    fn tensor_atmost(
+        &mut self,
+        elem: Self::Elem,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem {
+        let (head, shape) = elem.shape.split1();
+        let elems = elem
+            .elems
+            .split(head)
+            .iter()
+            .map(|v| self.bool_fold_atmost(v.iter(), bound, encoding))
+            .collect();
+        Tensor::new(shape, elems)
+    }
+
+    fn tensor_exactly(
+        &mut self,
+        elem: Self::Elem,
+        bound: usize,
+        encoding: CardEncoding,
+    ) -> Self::Elem {
+        let (head, shape) = elem.shape.split1();
+        let elems = elem
+            .elems
+            .split(head)
+            .iter()
+            .map(|v| self.bool_fold_exactly(v.iter(), bound, encoding))
+            .collect();
+        Tensor::new(shape, elems)
+    }
+
+    fn tensor_pb_atmost(
+        &mut self,
+        elem: Self::Elem,
+        weights: Tensor<i64>,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem {
+        assert_eq!(elem.shape, weights.shape);
+        let (head, shape) = elem.shape.split1();
+        let elems = elem
+            .elems
+            .split(head)
+            .iter()
+            .zip(weights.elems.split(head).iter())
+            .map(|(v, w)| self.bool_fold_pb_atmost(v.iter().zip(w.iter()), bound, encoding))
+            .collect();
+        Tensor::new(shape, elems)
+    }
+
+    fn tensor_pb_atleast(
+        &mut self,
+        elem: Self::Elem,
+        weights: Tensor<i64>,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem {
+        assert_eq!(elem.shape, weights.shape);
+        let (head, shape) = elem.shape.split1();
+        let elems = elem
+            .elems
+            .split(head)
+            .iter()
+            .zip(weights.elems.split(head).iter())
+            .map(|(v, w)| self.bool_fold_pb_atleast(v.iter().zip(w.iter()), bound, encoding))
+            .collect();
+        Tensor::new(shape, elems)
+    }
+
+    fn tensor_pb_exactly(
+        &mut self,
+        elem: Self::Elem,
+        weights: Tensor<i64>,
+        bound: i64,
+        encoding: PbEncoding,
+    ) -> Self::Elem {
+        assert_eq!(elem.shape, weights.shape);
+        let (head, shape) = elem.shape.split1();
+        let elems = elem
+            .elems
+            .split(head)
+            .iter()
+            .zip(weights.elems.split(head).iter())
+            .map(|(v, w)| self.bool_fold_pb_exactly(v.iter().zip(w.iter()), bound, encoding))
+            .collect();
+        Tensor::new(shape, elems)
+    }
 }
 
 /// The trait for solving tensor algebra problems.
@@ -513,7 +682,24 @@ pub trait TensorSat: TensorAlg {
         &mut self,
         assumptions: &[Self::Elem],
         elems: &[Self::Elem],
-    ) -> Option<Vec<Tensor<bool>>>;
+    ) -> Option<Vec<Tensor<bool>>> {
+        self.tensor_find_one_model_limited(assumptions, elems, &Default::default())
+            .expect("unlimited solver was interrupted")
+    }
+
+    /// Runs the solver with the given resource limits and returns a model if
+    /// it exists. An error is returned if the solver ran out of resources.
+    fn tensor_find_one_model_limited(
+        &mut self,
+        assumptions: &[Self::Elem],
+        elems: &[Self::Elem],
+        limits: &Limits,
+    ) -> Result<Option<Vec<Tensor<bool>>>, Interrupted>;
+
+    /// Returns the positions of the given assumptions that were used to
+    /// derive the unsatisfiability in the last call of the solver. The
+    /// shapes of the returned masks match the ones that were passed in.
+    fn tensor_failed_assumptions(&self, assumptions: &[Self::Elem]) -> Vec<Tensor<bool>>;
 
     /// Runs the solver and returns a model if it exists. The shapes of the
     /// returned tensors match the ones that were passed in.
@@ -543,8 +729,149 @@ pub trait TensorSat: TensorAlg {
         }
     }
 
+    /// Adds a clause to the solver that excludes the given values of the
+    /// given tensors, so every later model differs from them somewhere.
+    fn tensor_add_blocking_clause(&mut self, elems: &[Self::Elem], values: &[Tensor<bool>]);
+
+    /// Returns an iterator over the models satisfying the assumptions
+    /// projected onto the given tensors. Each returned projection is
+    /// permanently excluded from the solver.
+    fn tensor_models<'a>(
+        &'a mut self,
+        assumptions: &[Self::Elem],
+        elems: &[Self::Elem],
+    ) -> Models<'a, Self>
+    where
+        Self: Sized,
+    {
+        Models::new(self, assumptions, elems)
+    }
+
     /// Returns the number of models with respect to the given tensors.
     fn tensor_find_num_models(self, elems: &[Self::Elem]) -> usize;
+
+    /// Returns the number of models with respect to the given tensors by
+    /// solving cubes in parallel threads, see `BoolSat::bool_par_find_num_models`.
+    /// The split tensors select the positions to split on, which must be
+    /// among the given tensors.
+    fn tensor_par_find_num_models(
+        &self,
+        elems: &[Self::Elem],
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> usize;
+
+    /// Returns an iterator over the models with respect to the given
+    /// tensors found by parallel threads, see `tensor_par_find_num_models`.
+    /// The models arrive in no particular order.
+    fn tensor_par_models(
+        &self,
+        elems: &[Self::Elem],
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> Box<dyn Iterator<Item = Vec<Tensor<bool>>>>;
+
+    /// Returns the exact number of models with respect to the given tensors
+    /// without enumerating them, see `BoolSat::bool_count_models`.
+    fn tensor_count_models(&self, elems: &[Self::Elem]) -> BigNum;
+
+    /// Returns an estimate of the number of models with respect to the
+    /// given tensors that is within a factor of `1 + epsilon` of the real
+    /// count with probability at least `1 - delta`. The random XOR hash
+    /// functions are generated from the given seed. The clauses added
+    /// during the search are disabled afterwards, so the solver remains
+    /// usable.
+    fn tensor_approx_num_models(
+        &mut self,
+        elems: &[Self::Elem],
+        epsilon: f64,
+        delta: f64,
+        seed: u64,
+    ) -> ApproxCount;
+
+    /// Returns the given number of near-uniform random samples of the
+    /// models projected onto the given tensors, or an empty list if there
+    /// are no models. The random XOR hash functions and the choices are
+    /// generated from the given seed, so the samples are reproducible.
+    fn tensor_sample_models(
+        &mut self,
+        elems: &[Self::Elem],
+        count: usize,
+        seed: u64,
+    ) -> Vec<Vec<Tensor<bool>>>;
+
+    /// Finds a model under the given assumptions that minimizes the sum of
+    /// the weights of the true entries of the objective, and returns the
+    /// values of the given tensors in it. The weights must have the same
+    /// shape as the objective, and the time limit applies to the whole
+    /// search. If the resources run out, then the best model found so far
+    /// is returned, or an error if no model was found.
+    fn tensor_minimize(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objective: Self::Elem,
+        weights: Tensor<i64>,
+        elems: &[Self::Elem],
+        strategy: Strategy,
+        limits: &Limits,
+    ) -> Result<Option<Optimum>, Interrupted>;
+
+    /// Finds a model under the given assumptions that maximizes the sum of
+    /// the weights of the true entries of the objective, see
+    /// `tensor_minimize` for the details.
+    fn tensor_maximize(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objective: Self::Elem,
+        weights: Tensor<i64>,
+        elems: &[Self::Elem],
+        strategy: Strategy,
+        limits: &Limits,
+    ) -> Result<Option<Optimum>, Interrupted>;
+
+    /// Finds a model under the given assumptions that minimizes the given
+    /// objectives in lexicographic order, where each objective is a tensor
+    /// with a weight tensor of the same shape. The optimum of an objective
+    /// is kept with an assumption while the later ones are minimized, so
+    /// the constraints added to the solver remain reusable. If the resources
+    /// run out, then the best model for the current objective is returned,
+    /// or an error if no model was found.
+    fn tensor_minimize_lex(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objectives: &[(Self::Elem, Tensor<i64>)],
+        elems: &[Self::Elem],
+        strategy: Strategy,
+        limits: &Limits,
+    ) -> Result<Option<LexOptimum>, Interrupted>;
+
+    /// Finds a model under the given assumptions where the set of true
+    /// entries of the objective is minimal with respect to inclusion, and
+    /// returns the values of the given tensors in it. An error is returned
+    /// if the resources run out before a minimal model is found.
+    fn tensor_find_minimal_model(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objective: Self::Elem,
+        elems: &[Self::Elem],
+        limits: &Limits,
+    ) -> Result<Option<Vec<Tensor<bool>>>, Interrupted>;
+}
+
+/// Splits the given values into tensors of the same shapes as the given
+/// elements.
+fn split_values<ELEM>(elems: &[Tensor<ELEM>], values: GenVec<bool>) -> Vec<Tensor<bool>>
+where
+    ELEM: GenElem,
+{
+    let mut values = values.iter();
+    elems
+        .iter()
+        .map(|t| {
+            let size = t.shape().size();
+            Tensor::new(t.shape().clone(), values.by_ref().take(size).collect())
+        })
+        .collect()
 }
 
 impl<ALG> TensorSat for ALG
@@ -582,37 +909,179 @@ where
         }
     }
 
-    fn tensor_find_one_model(
+    fn tensor_find_one_model_limited(
         &mut self,
         assumptions: &[Self::Elem],
         elems: &[Self::Elem],
-    ) -> Option<Vec<Tensor<bool>>> {
+        limits: &Limits,
+    ) -> Result<Option<Vec<Tensor<bool>>>, Interrupted> {
         let ass2: Vec<ALG::Elem> = assumptions
             .iter()
             .map(|t| t.elems.iter())
             .flatten()
             .collect();
         let literals2 = elems.iter().map(|t| t.elems.iter()).flatten();
-        if let Some(values) = self.bool_find_one_model(&ass2, literals2) {
-            let mut result: Vec<Tensor<bool>> = Vec::with_capacity(elems.len());
-            let mut pos = 0;
-            for t in elems {
-                let size = t.shape().size();
-                result.push(Tensor::new(
-                    t.shape().clone(),
-                    values.iter().skip(pos).take(size).collect(),
-                ));
-                pos += size;
-            }
-            Some(result)
-        } else {
-            None
+        let values = self.bool_find_one_model_limited(&ass2, literals2, limits)?;
+        Ok(values.map(|values| split_values(elems, values)))
+    }
+
+    fn tensor_failed_assumptions(&self, assumptions: &[Self::Elem]) -> Vec<Tensor<bool>> {
+        let literals = assumptions.iter().flat_map(|t| t.elems.iter());
+        let values = self.bool_failed_assumptions(literals);
+        split_values(assumptions, values)
+    }
+
+    fn tensor_add_blocking_clause(&mut self, elems: &[Self::Elem], values: &[Tensor<bool>]) {
+        assert_eq!(elems.len(), values.len());
+        for (elem, value) in elems.iter().zip(values.iter()) {
+            assert_eq!(elem.shape, value.shape);
         }
+        let literals = elems.iter().flat_map(|t| t.elems.iter());
+        let values = values.iter().flat_map(|t| t.elems.iter());
+        self.bool_add_blocking_clause(literals.zip(values));
+    }
+
+    fn tensor_find_num_models(mut self, elems: &[Self::Elem]) -> usize {
+        self.tensor_models(&[], elems).count()
+    }
+
+    fn tensor_par_find_num_models(
+        &self,
+        elems: &[Self::Elem],
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> usize {
+        let literals = elems.iter().flat_map(|t| t.elems.iter());
+        let split: Vec<ALG::Elem> = split.iter().flat_map(|t| t.elems.iter()).collect();
+        self.bool_par_find_num_models(literals, &split, config)
+    }
+
+    fn tensor_par_models(
+        &self,
+        elems: &[Self::Elem],
+        split: &[Self::Elem],
+        config: &CubeConfig,
+    ) -> Box<dyn Iterator<Item = Vec<Tensor<bool>>>> {
+        let literals = elems.iter().flat_map(|t| t.elems.iter());
+        let split: Vec<ALG::Elem> = split.iter().flat_map(|t| t.elems.iter()).collect();
+        let shapes: Vec<Shape> = elems.iter().map(|t| t.shape.clone()).collect();
+        let models = self.bool_par_models(literals, &split, config);
+        Box::new(models.map(move |values| {
+            let mut values = values.into_iter();
+            shapes
+                .iter()
+                .map(|shape| {
+                    Tensor::new(shape.clone(), values.by_ref().take(shape.size()).collect())
+                })
+                .collect()
+        }))
+    }
+
+    fn tensor_count_models(&self, elems: &[Self::Elem]) -> BigNum {
+        self.bool_count_models(elems.iter().flat_map(|t| t.elems.iter()))
+    }
+
+    fn tensor_approx_num_models(
+        &mut self,
+        elems: &[Self::Elem],
+        epsilon: f64,
+        delta: f64,
+        seed: u64,
+    ) -> ApproxCount {
+        let literals = elems.iter().flat_map(|t| t.elems.iter()).collect();
+        approx::approx_count(self, literals, epsilon, delta, seed)
+    }
+
+    fn tensor_sample_models(
+        &mut self,
+        elems: &[Self::Elem],
+        count: usize,
+        seed: u64,
+    ) -> Vec<Vec<Tensor<bool>>> {
+        let literals = elems.iter().flat_map(|t| t.elems.iter()).collect();
+        sampler::sample(self, literals, count, seed)
+            .into_iter()
+            .map(|values| split_values(elems, values))
+            .collect()
+    }
+
+    fn tensor_minimize(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objective: Self::Elem,
+        weights: Tensor<i64>,
+        elems: &[Self::Elem],
+        strategy: Strategy,
+        limits: &Limits,
+    ) -> Result<Option<Optimum>, Interrupted> {
+        assert_eq!(objective.shape, weights.shape);
+        let ass2: Vec<ALG::Elem> = assumptions.iter().flat_map(|t| t.elems.iter()).collect();
+        let terms = objective.elems.iter().zip(weights.elems.iter()).collect();
+        let literals = elems.iter().flat_map(|t| t.elems.iter());
+        let result = optimize::minimize(self, &ass2, terms, literals, strategy, limits)?;
+        Ok(result.map(|result| Optimum {
+            cost: result.cost,
+            bound: result.lower_bound,
+            models: split_values(elems, result.values),
+        }))
+    }
+
+    fn tensor_maximize(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objective: Self::Elem,
+        weights: Tensor<i64>,
+        elems: &[Self::Elem],
+        strategy: Strategy,
+        limits: &Limits,
+    ) -> Result<Option<Optimum>, Interrupted> {
+        let weights = Tensor::new(weights.shape, weights.elems.iter().map(|w| -w).collect());
+        let result =
+            self.tensor_minimize(assumptions, objective, weights, elems, strategy, limits)?;
+        Ok(result.map(|result| Optimum {
+            cost: -result.cost,
+            bound: -result.bound,
+            models: result.models,
+        }))
     }
 
-    fn tensor_find_num_models(self, elems: &[Self::Elem]) -> usize {
-        let all_elems = elems.iter().map(|t| t.elems.iter()).flatten();
-        self.bool_find_num_models_method1(all_elems)
+    fn tensor_minimize_lex(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objectives: &[(Self::Elem, Tensor<i64>)],
+        elems: &[Self::Elem],
+        strategy: Strategy,
+        limits: &Limits,
+    ) -> Result<Option<LexOptimum>, Interrupted> {
+        let ass2: Vec<ALG::Elem> = assumptions.iter().flat_map(|t| t.elems.iter()).collect();
+        let objectives = objectives
+            .iter()
+            .map(|(objective, weights)| {
+                assert_eq!(objective.shape, weights.shape);
+                objective.elems.iter().zip(weights.elems.iter()).collect()
+            })
+            .collect();
+        let literals = elems.iter().flat_map(|t| t.elems.iter());
+        let result = optimize::minimize_lex(self, &ass2, objectives, literals, strategy, limits)?;
+        Ok(result.map(|result| LexOptimum {
+            costs: result.costs,
+            num_optimal: result.num_optimal,
+            models: split_values(elems, result.values),
+        }))
+    }
+
+    fn tensor_find_minimal_model(
+        &mut self,
+        assumptions: &[Self::Elem],
+        objective: Self::Elem,
+        elems: &[Self::Elem],
+        limits: &Limits,
+    ) -> Result<Option<Vec<Tensor<bool>>>, Interrupted> {
+        let ass2: Vec<ALG::Elem> = assumptions.iter().flat_map(|t| t.elems.iter()).collect();
+        let objective = objective.elems.iter().collect();
+        let literals = elems.iter().flat_map(|t| t.elems.iter());
+        let result = optimize::find_minimal_model(self, &ass2, objective, literals, limits)?;
+        Ok(result.map(|values| split_values(elems, values)))
     }
 }
 
@@ -620,7 +1089,7 @@ where
 mod tests {
     use std::iter;
 
-    use super::super::Boolean;
+    use super::super::{Boolean, Solver};
     use super::*;
 
     #[test]
@@ -695,4 +1164,64 @@ mod tests {
         assert_eq!(t3.shape, Shape::new(vec![]));
         assert_eq!(t3.very_slow_get(&[]), false);
     }
+
+    #[test]
+    fn failed() {
+        let mut alg = Solver::new("");
+        let t1 = alg.tensor_add_variable(Shape::new(vec![2, 2]));
+        let lit = alg.bool_not(t1.elems.get(2));
+        alg.bool_add_clause(&[lit]);
+
+        let result = alg.tensor_find_one_model(std::slice::from_ref(&t1), &[]);
+        assert!(result.is_none());
+        let mut masks = alg.tensor_failed_assumptions(&[t1]);
+        assert_eq!(masks.len(), 1);
+        let t2 = Tensor::create(Shape::new(vec![2, 2]), |c| c[0] == 0 && c[1] == 1);
+        assert_eq!(masks.pop().unwrap(), t2);
+    }
+
+    #[test]
+    fn cardinality() {
+        for encoding in [
+            CardEncoding::Counter,
+            CardEncoding::Totalizer,
+            CardEncoding::Sorter,
+        ] {
+            let mut alg = Solver::new("");
+            let t1 = alg.tensor_add_variable(Shape::new(vec![5, 2]));
+            let t2 = alg.tensor_atleast(t1.clone(), 2, encoding);
+            let t3 = alg.tensor_atmost(t1.clone(), 3, encoding);
+            let t4 = alg.tensor_and(t2, t3);
+            let t4 = alg.tensor_all(t4);
+            alg.tensor_add_clause1(t4);
+            assert_eq!(alg.tensor_find_num_models(&[t1]), 20 * 20);
+
+            let mut alg = Solver::new("");
+            let t1 = alg.tensor_add_variable(Shape::new(vec![4, 3]));
+            let t2 = alg.tensor_exactly(t1.clone(), 1, encoding);
+            let t2 = alg.tensor_all(t2);
+            alg.tensor_add_clause1(t2);
+            assert_eq!(alg.tensor_find_num_models(&[t1]), 4 * 4 * 4);
+        }
+    }
+
+    #[test]
+    fn pseudo_boolean() {
+        for encoding in [PbEncoding::Bdd, PbEncoding::Adder, PbEncoding::Sorter] {
+            let weights = Tensor::create(Shape::new(vec![4, 3]), |c| c[0] as i64 + 1);
+            let mut alg = Solver::new("");
+            let t1 = alg.tensor_add_variable(Shape::new(vec![4, 3]));
+            let t2 = alg.tensor_pb_exactly(t1.clone(), weights, 3, encoding);
+            let t2 = alg.tensor_all(t2);
+            alg.tensor_add_clause1(t2);
+            assert_eq!(alg.tensor_find_num_models(&[t1]), 2 * 2 * 2);
+
+            let weights = Tensor::create(Shape::new(vec![3]), |c| c[0] as i64 - 1);
+            let mut alg = Solver::new("");
+            let t1 = alg.tensor_add_variable(Shape::new(vec![3]));
+            let t2 = alg.tensor_pb_atleast(t1.clone(), weights.clone(), 0, encoding);
+            alg.tensor_add_clause1(t2);
+            assert_eq!(alg.tensor_find_num_models(&[t1]), 6);
+        }
+    }
 }
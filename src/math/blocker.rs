@@ -19,8 +19,8 @@
 
 use super::{binrel, BinaryRel};
 use crate::core::{
-    add_progress, del_progress, set_progress, Boolean, Literal, Shape, Solver, Tensor, TensorAlg,
-    TensorSat,
+    add_progress, del_progress, set_progress, Boolean, Interrupted, Limits, Literal, Shape, Solver,
+    Tensor, TensorAlg, TensorSat,
 };
 
 struct Extension {
@@ -55,22 +55,53 @@ impl Extension {
         }
     }
 
-    pub fn find(&mut self, source_graph: Tensor<bool>) -> Option<Tensor<bool>> {
+    pub fn find(
+        &mut self,
+        source_graph: Tensor<bool>,
+        limits: &Limits,
+    ) -> Result<Option<Tensor<bool>>, Interrupted> {
         let source_graph = self.alg.tensor_lift(source_graph);
         let source_graph = self.alg.tensor_equ(source_graph, self.source_graph.clone());
-        let result = self
-            .alg
-            .tensor_find_one_model(&[source_graph], &[self.extension_map.clone()]);
+        let result = self.alg.tensor_find_one_model_limited(
+            &[source_graph],
+            &[self.extension_map.clone()],
+            limits,
+        )?;
 
-        result.map(|mut v| {
+        Ok(result.map(|mut v| {
             assert_eq!(v.len(), 1);
             v.pop().unwrap()
-        })
+        }))
+    }
+
+    /// Returns the positions of the source graph (both edges and non-edges)
+    /// that together prevent an extension, or `None` if an extension exists.
+    pub fn explain(
+        &mut self,
+        source_graph: Tensor<bool>,
+        limits: &Limits,
+    ) -> Result<Option<Tensor<bool>>, Interrupted> {
+        let source_graph = self.alg.tensor_lift(source_graph);
+        let source_graph = self.alg.tensor_equ(source_graph, self.source_graph.clone());
+        let result = self.alg.tensor_find_one_model_limited(
+            std::slice::from_ref(&source_graph),
+            &[],
+            limits,
+        )?;
+
+        if result.is_some() {
+            Ok(None)
+        } else {
+            let mut masks = self.alg.tensor_failed_assumptions(&[source_graph]);
+            assert_eq!(masks.len(), 1);
+            Ok(masks.pop())
+        }
     }
 }
 
 pub struct Blocker {
     trace: bool,
+    limits: Limits,
     solver_name: String,
     partial_map: Tensor<bool>,
     target_graph: Tensor<bool>,
@@ -88,6 +119,7 @@ impl Blocker {
 
         Blocker {
             trace: false,
+            limits: Default::default(),
             solver_name: solver_name.into(),
             partial_map,
             target_graph,
@@ -95,6 +127,12 @@ impl Blocker {
         }
     }
 
+    /// Sets the resource limits for each call of the SAT solvers, so that
+    /// long searches give up instead of running forever.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
     pub fn source_size(&self) -> usize {
         self.partial_map.shape()[0]
     }
@@ -123,11 +161,23 @@ impl Blocker {
         alg.tensor_find_one_model1(map)
     }
 
-    pub fn find_extension2(&mut self, source_graph: Tensor<bool>) -> Option<Tensor<bool>> {
-        self.extension.find(source_graph)
+    pub fn find_extension2(
+        &mut self,
+        source_graph: Tensor<bool>,
+    ) -> Result<Option<Tensor<bool>>, Interrupted> {
+        self.extension.find(source_graph, &self.limits)
     }
 
-    pub fn find_source_graph(&mut self) -> Option<Tensor<bool>> {
+    /// Returns the positions of the source graph responsible for the lack of
+    /// an extension of the partial map, or `None` if an extension exists.
+    pub fn explain_extension(
+        &mut self,
+        source_graph: Tensor<bool>,
+    ) -> Result<Option<Tensor<bool>>, Interrupted> {
+        self.extension.explain(source_graph, &self.limits)
+    }
+
+    pub fn find_source_graph(&mut self) -> Result<Option<Tensor<bool>>, Interrupted> {
         let mut alg = Solver::new(&self.solver_name);
 
         let target_graph2 = alg.tensor_lift(self.target_graph.clone());
@@ -161,14 +211,19 @@ impl Blocker {
 
         let mut excluded = 0;
         let mut minimal = None;
-        loop {
-            let result = alg.tensor_find_one_model1(source_graph.clone());
-            if result.is_none() {
-                break;
-            }
-
-            let result = result.unwrap();
-            let extension = self.find_extension2(result.clone());
+        let result = loop {
+            let elems = std::slice::from_ref(&source_graph);
+            let result = alg.tensor_find_one_model_limited(&[], elems, &self.limits);
+            let result = match result {
+                Ok(Some(mut result)) => result.pop().unwrap(),
+                Ok(None) => break Ok(minimal),
+                Err(err) => break Err(err),
+            };
+
+            let extension = match self.find_extension2(result.clone()) {
+                Ok(extension) => extension,
+                Err(err) => break Err(err),
+            };
             if extension.is_none() {
                 minimal = Some(result.clone());
                 let result = alg.tensor_lift(result);
@@ -186,10 +241,10 @@ impl Blocker {
             let tmp = alg.is_compatible(extension, source_graph.clone(), target_graph2.clone());
             let tmp = alg.tensor_not(tmp);
             alg.tensor_add_clause1(tmp);
-        }
+        };
 
         del_progress("excluded");
-        minimal
+        result
     }
 }
 
@@ -200,14 +255,15 @@ pub fn test() {
     let partial_map = [0, 0, 0, 1, 3, 4, -1, -1, -1, -1, -1];
     let mut blocker = Blocker::new("cadical", &partial_map, target_graph);
 
-    let source_graph = blocker.find_source_graph();
-    if source_graph.is_none() {
-        println!("source: None");
-    } else {
-        println!(
-            "source: {:?}",
-            binrel::edges(source_graph.as_ref().unwrap())
-        );
+    match blocker.find_source_graph() {
+        Err(err) => println!("source: {}", err),
+        Ok(None) => println!("source: None"),
+        Ok(Some(source_graph)) => {
+            println!("source: {:?}", binrel::edges(&source_graph));
+            if let Ok(Some(reason)) = blocker.explain_extension(source_graph) {
+                println!("reason: {:?}", binrel::edges(&reason));
+            }
+        }
     }
     println!("target: {:?}", binrel::edges(&blocker.target_graph));
 }
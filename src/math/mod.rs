@@ -25,5 +25,5 @@ mod validate;
 
 pub use binrel::BinaryRel;
 pub use blocker::test;
-pub use validate::validate;
-pub use obstruction::test as obstruction_test;
\ No newline at end of file
+pub use obstruction::test as obstruction_test;
+pub use validate::{validate, validate_bdd, validate_counter, validate_parallel};
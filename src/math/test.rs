@@ -45,12 +45,8 @@ pub fn test1() {
     let tmp = alg.is_compatible(fun.clone(), rel.clone(), rel);
     alg.tensor_add_clause(&[tmp]);
 
-    while let Some(sol) = alg.tensor_find_one_model1(fun.clone()) {
-        println!("{:?}", sol);
-        let tmp = alg.tensor_lift(sol);
-        let tmp = alg.is_equal_to(tmp, fun.clone());
-        let tmp = alg.tensor_not(tmp);
-        alg.tensor_add_clause(&[tmp]);
+    for mut sol in alg.tensor_models(&[], &[fun]) {
+        println!("{:?}", sol.pop().unwrap());
     }
 
     // let num = alg.tensor_find_num_models(&[fun]);
@@ -18,20 +18,61 @@
 use std::time::Instant;
 
 use super::BinaryRel;
-use crate::core::{Shape, Solver, TensorAlg, TensorSat};
+use crate::core::{Bdd, BddNode, BigNum, BitParallel, Shape, Solver, Tensor, TensorAlg, TensorSat};
 
 fn check(
     solver: &str,
     desc: &str,
     shape: Shape,
     pred: fn(&mut Solver, elem: <Solver as TensorAlg>::Elem) -> <Solver as TensorAlg>::Elem,
-    count: usize,
+    count: u64,
 ) {
     let mut sol = Solver::new(solver);
     let elem = sol.tensor_add_variable(shape.clone());
     let cond = pred(&mut sol, elem.clone());
     sol.tensor_add_clause(&[cond]);
-    let num = sol.tensor_find_num_models(&[elem]);
+
+    // recorded formulas are counted exactly without enumeration
+    let num = if sol.get_formula().is_some() {
+        sol.tensor_count_models(&[elem])
+    } else {
+        BigNum::from(sol.tensor_find_num_models(&[elem]) as u64)
+    };
+    println!("Number of {} of shape {:?} is {}", desc, shape.dims(), num);
+    assert_eq!(num, BigNum::from(count));
+}
+
+fn check_bdd(
+    desc: &str,
+    shape: Shape,
+    pred: fn(&mut Bdd, elem: <Bdd as TensorAlg>::Elem) -> <Bdd as TensorAlg>::Elem,
+    count: u64,
+) {
+    let mut bdd = Bdd::new();
+    let elem: Tensor<BddNode> = Tensor::create(shape.clone(), |_| bdd.add_variable());
+    let cond = pred(&mut bdd, elem);
+    let num = bdd.sat_count(cond.scalar());
+    println!(
+        "Number of {} of shape {:?} is {} with {} nodes",
+        desc,
+        shape.dims(),
+        num,
+        bdd.num_nodes()
+    );
+    assert_eq!(num, BigNum::from(count));
+}
+
+fn check_parallel(
+    desc: &str,
+    shape: Shape,
+    pred: fn(&mut BitParallel, elem: Tensor<u64>) -> Tensor<u64>,
+    count: u64,
+) {
+    let num = BitParallel::count_models(shape.size(), |alg, vars| {
+        let mut iter = vars.iter().copied();
+        let elem = Tensor::create(shape.clone(), |_| iter.next().unwrap());
+        pred(alg, elem).scalar()
+    });
     println!("Number of {} of shape {:?} is {}", desc, shape.dims(), num);
     assert_eq!(num, count);
 }
@@ -85,6 +126,144 @@ pub fn validate_solver(solver: &str) {
     println!("Solver {} finished in {} seconds\n", solver, duration);
 }
 
+/// Validates the exact model counter on larger shapes where enumerating
+/// the models would take too long.
+pub fn validate_counter() {
+    let start = Instant::now();
+
+    check(
+        "record:",
+        "transitive relations",
+        Shape::new(vec![5, 5]),
+        <Solver as BinaryRel>::is_transitive,
+        154303,
+    );
+
+    check(
+        "record:",
+        "equivalence relations",
+        Shape::new(vec![10, 10]),
+        <Solver as BinaryRel>::is_equivalence,
+        115975,
+    );
+
+    check(
+        "record:",
+        "partial orders",
+        Shape::new(vec![6, 6]),
+        <Solver as BinaryRel>::is_partial_order,
+        130023,
+    );
+
+    check(
+        "record:",
+        "functions",
+        Shape::new(vec![8, 8]),
+        <Solver as BinaryRel>::is_function,
+        16777216,
+    );
+
+    check(
+        "record:",
+        "partial functions",
+        Shape::new(vec![7, 7]),
+        <Solver as BinaryRel>::is_partial_function,
+        2097152,
+    );
+
+    let duration = Instant::now().duration_since(start).as_secs_f32();
+    println!("Model counter finished in {} seconds\n", duration);
+}
+
+/// Validates the binary decision diagrams on the same predicates.
+pub fn validate_bdd() {
+    let start = Instant::now();
+
+    check_bdd(
+        "transitive relations",
+        Shape::new(vec![4, 4]),
+        <Bdd as BinaryRel>::is_transitive,
+        3994,
+    );
+
+    check_bdd(
+        "equivalence relations",
+        Shape::new(vec![6, 6]),
+        <Bdd as BinaryRel>::is_equivalence,
+        203,
+    );
+
+    check_bdd(
+        "partial orders",
+        Shape::new(vec![5, 5]),
+        <Bdd as BinaryRel>::is_partial_order,
+        4231,
+    );
+
+    check_bdd(
+        "functions",
+        Shape::new(vec![5, 6]),
+        <Bdd as BinaryRel>::is_function,
+        7776,
+    );
+
+    check_bdd(
+        "partial functions",
+        Shape::new(vec![5, 5]),
+        <Bdd as BinaryRel>::is_partial_function,
+        7776,
+    );
+
+    let duration = Instant::now().duration_since(start).as_secs_f32();
+    println!(
+        "Binary decision diagrams finished in {} seconds\n",
+        duration
+    );
+}
+
+/// Validates the bit-parallel algebra by checking all assignments.
+pub fn validate_parallel() {
+    let start = Instant::now();
+
+    check_parallel(
+        "transitive relations",
+        Shape::new(vec![4, 4]),
+        <BitParallel as BinaryRel>::is_transitive,
+        3994,
+    );
+
+    check_parallel(
+        "equivalence relations",
+        Shape::new(vec![4, 4]),
+        <BitParallel as BinaryRel>::is_equivalence,
+        15,
+    );
+
+    check_parallel(
+        "partial orders",
+        Shape::new(vec![4, 4]),
+        <BitParallel as BinaryRel>::is_partial_order,
+        219,
+    );
+
+    check_parallel(
+        "functions",
+        Shape::new(vec![4, 4]),
+        <BitParallel as BinaryRel>::is_function,
+        256,
+    );
+
+    check_parallel(
+        "partial functions",
+        Shape::new(vec![4, 4]),
+        <BitParallel as BinaryRel>::is_partial_function,
+        625,
+    );
+
+    let duration = Instant::now().duration_since(start).as_secs_f32();
+    println!("Bit-parallel evaluation finished in {} seconds\n", duration);
+}
+
 pub fn validate() {
     #[cfg(feature = "cadical")]
     validate_solver("cadical");
@@ -98,4 +277,8 @@ pub fn validate() {
     validate_solver("varisat");
     #[cfg(feature = "cryptominisat")]
     validate_solver("cryptominisat");
+    validate_solver("portfolio");
+    validate_counter();
+    validate_bdd();
+    validate_parallel();
 }